@@ -3,6 +3,30 @@ use clap::Parser;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// filename that you want to execute
+    /// filename that you want to execute, `-` reads it from stdin
     pub file: Option<String>,
+
+    /// run this program instead of a file or the prompt
+    #[arg(short, long, value_name = "PROGRAM")]
+    pub eval: Option<String>,
+
+    /// only report syntax errors in the file, without executing it
+    #[arg(long, requires = "file")]
+    pub check: bool,
+
+    /// print the language constructs this build supports and exit
+    #[arg(long)]
+    pub features: bool,
+
+    /// write every executed statement and function call to stderr
+    #[arg(long)]
+    pub trace: bool,
+
+    /// print the tokens of the file, or of stdin without a file, and exit
+    #[arg(long, alias = "dump-tokens")]
+    pub tokens: bool,
+
+    /// print the syntax tree of each top-level statement instead of executing it
+    #[arg(long)]
+    pub ast: bool,
 }
@@ -1,75 +1,353 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
-    error::{ParseError, RuntimeError},
-    expression::{self, LiteralValue},
-    token::Token,
+    error::RuntimeError, expression::LiteralValue, statement::TypeAnnotation, token::Token,
 };
 
+/// default upper bound of the scope chain length, the global scope included
+pub const DEFAULT_MAX_SCOPE_DEPTH: usize = 1024;
+
+/// a scope shared by the environment and the closures declared in it
+pub type ScopeRef = Rc<RefCell<Scope>>;
+
 pub struct Environment {
-    pub scopes: Vec<Scope>,
+    /// the innermost scope, linked to the enclosing ones up to the global scope
+    current: ScopeRef,
+    /// the longest scope chain allowed, so runaway nesting fails cleanly
+    pub max_depth: usize,
 }
 
 pub struct Scope {
     values: HashMap<String, LiteralValue>,
+    /// declared types of the annotated bindings in `values`
+    annotations: HashMap<String, TypeAnnotation>,
+    /// the enclosing scope, `None` for the global scope
+    parent: Option<ScopeRef>,
+    /// the length of the chain ending here, 1 for the global scope
+    depth: usize,
 }
 
 impl Scope {
     pub fn new() -> Self {
         Self {
             values: HashMap::new(),
+            annotations: HashMap::new(),
+            parent: None,
+            depth: 1,
+        }
+    }
+
+    /// `None` if `name` is not bound here
+    fn assign(&mut self, name: &Token, value: &LiteralValue) -> Option<Result<(), RuntimeError>> {
+        let annotation = self.annotations.get(&name.lexeme).copied();
+        let v = self.values.get_mut(&name.lexeme)?;
+        if let Some(annotation) = annotation {
+            if let Err(err) = check_type(&name.lexeme, annotation, value, name.line) {
+                return Some(Err(err));
+            }
+        }
+        *v = value.clone();
+        Some(Ok(()))
+    }
+
+    fn with_parent(parent: ScopeRef) -> Self {
+        let depth = parent.borrow().depth + 1;
+        Self {
+            parent: Some(parent),
+            depth,
+            ..Self::new()
         }
     }
 }
 
+impl Default for Scope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Environment {
     pub fn new() -> Self {
         // with the global scope
         Self {
-            scopes: vec![Scope::new()],
+            current: Rc::new(RefCell::new(Scope::new())),
+            max_depth: DEFAULT_MAX_SCOPE_DEPTH,
         }
     }
 }
 
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Environment {
-    pub fn get(&self, name: &str) -> Result<&LiteralValue, RuntimeError> {
-        for scope in self.scopes.iter().rev() {
-            if let Some(v) = scope.values.get(name) {
-                return Ok(v);
+    pub fn get(&self, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        check_name(&name.lexeme, name.line)?;
+        self.find(|scope| scope.values.get(&name.lexeme).cloned())
+            .ok_or_else(|| undefined(name))
+    }
+
+    /// look `name` up only in the scope `hops` levels out, as computed by the resolver
+    pub fn get_at(&self, hops: usize, name: &Token) -> Result<LiteralValue, RuntimeError> {
+        check_name(&name.lexeme, name.line)?;
+        let scope = self.ancestor(hops);
+        let value = scope.borrow().values.get(&name.lexeme).cloned();
+        value.ok_or_else(|| undefined(name))
+    }
+
+    /// the names bound in the global scope
+    pub fn global_names(&self) -> Vec<String> {
+        let mut scope = Rc::clone(&self.current);
+        loop {
+            let parent = scope.borrow().parent.clone();
+            match parent {
+                Some(parent) => scope = parent,
+                None => break,
             }
         }
+        let names = scope.borrow().values.keys().cloned().collect();
+        names
+    }
+
+    /// whether `name` is bound in any enclosing scope, never errors
+    pub fn contains(&self, name: &str) -> bool {
+        self.find(|scope| scope.values.contains_key(name).then_some(()))
+            .is_some()
+    }
 
-        Err(RuntimeError::new(format!("Undefined variable `{}`.", name)))
+    /// `line` locates the declaration for errors,
+    /// a name may only be redeclared in the global scope
+    pub fn define(
+        &mut self,
+        name: &str,
+        value: LiteralValue,
+        line: usize,
+    ) -> Result<(), RuntimeError> {
+        check_name(name, line)?;
+        self.check_redeclaration(name, line)?;
+        let mut scope = self.current.borrow_mut();
+        scope.values.insert(name.to_string(), value);
+        scope.annotations.remove(name);
+        Ok(())
     }
 
-    pub fn define(&mut self, name: &str, value: LiteralValue) {
-        let last_scope = self
-            .scopes
-            .last_mut()
-            .expect("Interpretor must have a scope.");
-        last_scope.values.insert(name.to_string(), value);
+    /// define a binding whose later assignments must match `annotation`
+    pub fn define_typed(
+        &mut self,
+        name: &str,
+        value: LiteralValue,
+        annotation: TypeAnnotation,
+        line: usize,
+    ) -> Result<(), RuntimeError> {
+        check_name(name, line)?;
+        self.check_redeclaration(name, line)?;
+        check_type(name, annotation, &value, line)?;
+        let mut scope = self.current.borrow_mut();
+        scope.values.insert(name.to_string(), value);
+        scope.annotations.insert(name.to_string(), annotation);
+        Ok(())
     }
 
     pub fn assign(&mut self, name: Token, value: LiteralValue) -> Result<(), RuntimeError> {
-        for scope in self.scopes.iter_mut().rev() {
-            if scope.values.contains_key(&name.lexeme) {
-                scope.values.insert(name.lexeme, value);
-                return Ok(());
+        check_name(&name.lexeme, name.line)?;
+        self.find(|scope| scope.assign(&name, &value))
+            .unwrap_or_else(|| Err(undefined_assign(&name)))
+    }
+
+    /// assign only in the scope `hops` levels out, as computed by the resolver
+    pub fn assign_at(
+        &mut self,
+        hops: usize,
+        name: Token,
+        value: LiteralValue,
+    ) -> Result<(), RuntimeError> {
+        check_name(&name.lexeme, name.line)?;
+        let scope = self.ancestor(hops);
+        let assigned = scope.borrow_mut().assign(&name, &value);
+        assigned.unwrap_or_else(|| Err(undefined_assign(&name)))
+    }
+
+    fn ancestor(&self, hops: usize) -> ScopeRef {
+        let mut scope = Rc::clone(&self.current);
+        for _ in 0..hops {
+            let parent = scope
+                .borrow()
+                .parent
+                .clone()
+                .expect("resolved scope hops must stay within the scope chain");
+            scope = parent;
+        }
+        scope
+    }
+
+    fn check_redeclaration(&self, name: &str, line: usize) -> Result<(), RuntimeError> {
+        let scope = self.current.borrow();
+        if scope.parent.is_some() && scope.values.contains_key(name) {
+            return Err(RuntimeError::new(
+                format!("Variable `{}` is already declared in this scope.", name),
+                line,
+            ));
+        }
+        Ok(())
+    }
+
+    /// the first `Some` of `f` over the scopes from the innermost outwards
+    fn find<T>(&self, mut f: impl FnMut(&mut Scope) -> Option<T>) -> Option<T> {
+        let mut scope = Rc::clone(&self.current);
+        loop {
+            if let Some(found) = f(&mut scope.borrow_mut()) {
+                return Some(found);
             }
+            let parent = scope.borrow().parent.clone()?;
+            scope = parent;
         }
-        Err(RuntimeError::new(format!(
-            "Undefined variable `{}`.",
-            name.lexeme
-        )))
     }
 
-    /// called when enter a new block
-    pub fn create_scope(&mut self) {
-        self.scopes.push(Scope::new());
+    /// called when enter a new block, which starts at `line`
+    pub fn create_scope(&mut self, line: usize) -> Result<(), RuntimeError> {
+        if self.depth() >= self.max_depth {
+            return Err(RuntimeError::new(
+                "Scope nesting limit exceeded.".to_string(),
+                line,
+            ));
+        }
+        let scope = Scope::with_parent(Rc::clone(&self.current));
+        self.current = Rc::new(RefCell::new(scope));
+        Ok(())
     }
 
-    /// called when finish a block
+    /// called when finish a block, the global scope is never dropped
     pub fn drop_scope(&mut self) {
-        self.scopes.pop();
+        let parent = self.current.borrow().parent.clone();
+        if let Some(parent) = parent {
+            self.current = parent;
+        }
+    }
+
+    /// the number of scopes visible from the innermost one, the global scope included
+    pub fn depth(&self) -> usize {
+        self.current.borrow().depth
+    }
+
+    /// the innermost scope, for a closure to keep alive
+    pub fn current_scope(&self) -> ScopeRef {
+        Rc::clone(&self.current)
     }
+
+    /// make `scope` the innermost one, returning the scope it replaces
+    pub fn replace_scope(&mut self, scope: ScopeRef) -> ScopeRef {
+        std::mem::replace(&mut self.current, scope)
+    }
+}
+
+fn undefined(name: &Token) -> RuntimeError {
+    RuntimeError::new(format!("Undefined variable `{}`.", name.lexeme), name.line)
+}
+
+fn undefined_assign(name: &Token) -> RuntimeError {
+    RuntimeError::new(
+        format!(
+            "Undefined variable `{}`. help: declare it first with `var {} = ...;`",
+            name.lexeme, name.lexeme
+        ),
+        name.line,
+    )
+}
+
+/// an empty name can only come from a scanner/parser bug,
+/// and a binding under it could never be read back
+fn check_name(name: &str, line: usize) -> Result<(), RuntimeError> {
+    if name.trim().is_empty() {
+        Err(RuntimeError::new(
+            format!("Internal error: invalid variable name `{}`.", name),
+            line,
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_type(
+    name: &str,
+    annotation: TypeAnnotation,
+    value: &LiteralValue,
+    line: usize,
+) -> Result<(), RuntimeError> {
+    if annotation.accepts(value) {
+        Ok(())
+    } else {
+        Err(RuntimeError::new(
+            format!(
+                "Variable `{}` is declared as `{}`, but got a `{}`.",
+                name,
+                annotation,
+                value.type_name()
+            ),
+            line,
+        ))
+    }
+}
+
+#[test]
+fn test_reject_empty_name() {
+    let mut env = Environment::new();
+    assert!(env.define("", LiteralValue::Nil, 1).is_err());
+    assert!(env.define("  ", LiteralValue::Nil, 1).is_err());
+    let empty = Token::new(crate::token::TokenType::Identifier, String::new(), 1, 1);
+    assert!(env.get(&empty).is_err());
+    assert!(env.assign(empty, LiteralValue::Nil).is_err());
+
+    assert!(env.define("a", LiteralValue::Nil, 1).is_ok());
+    let a = Token::new(crate::token::TokenType::Identifier, "a".to_string(), 1, 1);
+    assert_eq!(env.get(&a).unwrap(), LiteralValue::Nil);
+}
+
+#[test]
+fn test_contains() {
+    let mut env = Environment::new();
+    env.define("a", LiteralValue::Nil, 1).unwrap();
+    env.create_scope(1).unwrap();
+    env.define("b", LiteralValue::Bool(true), 1).unwrap();
+    assert!(env.contains("a"));
+    assert!(env.contains("b"));
+    assert!(!env.contains("c"));
+    env.drop_scope();
+    assert!(!env.contains("b"));
+}
+
+#[test]
+fn test_assign_undefined_suggestion() {
+    let mut env = Environment::new();
+    let name = Token::new(crate::token::TokenType::Identifier, "a".to_string(), 4, 1);
+    let err = env.assign(name, LiteralValue::Nil).unwrap_err();
+    assert_eq!(err.line(), 4);
+    assert_eq!(
+        err.message(),
+        "Undefined variable `a`. help: declare it first with `var a = ...;`"
+    );
+}
+
+#[test]
+fn test_local_redeclaration() {
+    let mut env = Environment::new();
+    env.define("a", LiteralValue::Nil, 1).unwrap();
+    env.define("a", LiteralValue::Bool(true), 2).unwrap();
+
+    env.create_scope(3).unwrap();
+    env.define("a", LiteralValue::Nil, 3).unwrap();
+    let err = env.define("a", LiteralValue::Nil, 4).unwrap_err();
+    assert_eq!(err.line(), 4);
+    assert_eq!(
+        err.message(),
+        "Variable `a` is already declared in this scope."
+    );
+    assert!(env
+        .define_typed("a", LiteralValue::Nil, TypeAnnotation::Nil, 5)
+        .is_err());
+    env.drop_scope();
+
+    let a = Token::new(crate::token::TokenType::Identifier, "a".to_string(), 6, 1);
+    assert_eq!(env.get(&a).unwrap(), LiteralValue::Bool(true));
 }
@@ -1,8 +1,35 @@
 #[derive(Debug)]
 pub enum LoxError {
-    // TokenError(),
-    ParseError(ParseError),
+    ScanErrors(Vec<ScanError>),
+    ParseErrors(Vec<ParseError>),
     RuntimeError(RuntimeError),
+    /// a source file that could not be read, with its path
+    Io(String, std::io::Error),
+}
+
+#[derive(Debug)]
+pub struct ScanError {
+    /// the invalid source text, or a description of it
+    lexeme: String,
+    line: usize,
+}
+
+impl ScanError {
+    pub fn report(&self) {
+        println!("Error: {}", self);
+    }
+
+    pub fn new(lexeme: String, line: usize) -> Self {
+        Self { lexeme, line }
+    }
+
+    pub fn lexeme(&self) -> &str {
+        &self.lexeme
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
 }
 
 #[derive(Debug)]
@@ -12,7 +39,7 @@ pub struct ParseError {
 
 impl ParseError {
     pub fn report(&self) {
-        println!("Error: {}", self.message);
+        println!("Error: {}", self);
     }
 
     pub fn new(msg: String) -> Self {
@@ -23,23 +50,158 @@ impl ParseError {
 #[derive(Debug)]
 pub struct RuntimeError {
     message: String,
+    /// the line of the token the error was raised at
+    line: usize,
+    /// raised by `panic`, never recovered from by `continue_on_error`, the REPL or a `try`
+    fatal: bool,
 }
 
 impl RuntimeError {
     pub fn report(&self) {
-        println!("RuntimeError: {}", self.message);
+        println!("RuntimeError: {}", self);
     }
 
-    pub fn new(msg: String) -> Self {
-        Self { message: msg }
+    pub fn new(msg: String, line: usize) -> Self {
+        Self {
+            message: msg,
+            line,
+            fatal: false,
+        }
+    }
+
+    pub fn fatal(msg: String, line: usize) -> Self {
+        Self {
+            message: msg,
+            line,
+            fatal: true,
+        }
+    }
+
+    pub fn is_fatal(&self) -> bool {
+        self.fatal
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
     }
 }
 
 impl LoxError {
+    /// the conventional Lox exit status: 65 for static errors, 70 at runtime, 74 for I/O
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LoxError::ScanErrors(_) | LoxError::ParseErrors(_) => 65,
+            LoxError::RuntimeError(_) => 70,
+            LoxError::Io(..) => 74,
+        }
+    }
+
     pub fn report(&self) {
         match self {
-            LoxError::ParseError(err) => err.report(),
+            LoxError::ScanErrors(errs) => errs.iter().for_each(|err| err.report()),
+            LoxError::ParseErrors(errs) => errs.iter().for_each(|err| err.report()),
             LoxError::RuntimeError(err) => err.report(),
+            LoxError::Io(..) => println!("Error: {}", self),
+        }
+    }
+}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] Invalid token: {}", self.line, self.lexeme)
+    }
+}
+
+/// the message carries the `[line N]` prefix
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] {}", self.line, self.message)
+    }
+}
+
+/// one line per scan or parse error
+impl std::fmt::Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::ScanErrors(errs) => write_error_lines(f, errs),
+            LoxError::ParseErrors(errs) => write_error_lines(f, errs),
+            LoxError::RuntimeError(err) => write!(f, "RuntimeError: {}", err),
+            LoxError::Io(path, _) => write!(f, "Could not open file: {}", path),
+        }
+    }
+}
+
+fn write_error_lines<E: std::fmt::Display>(
+    f: &mut std::fmt::Formatter<'_>,
+    errs: &[E],
+) -> std::fmt::Result {
+    for (i, err) in errs.iter().enumerate() {
+        if i > 0 {
+            writeln!(f)?;
         }
+        write!(f, "Error: {}", err)?;
     }
+    Ok(())
+}
+
+impl std::error::Error for ScanError {}
+
+impl std::error::Error for ParseError {}
+
+impl std::error::Error for RuntimeError {}
+
+impl std::error::Error for LoxError {
+    /// the runtime or I/O error, or the first of the scan or parse errors
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoxError::ScanErrors(errs) => errs
+                .first()
+                .map(|err| err as &(dyn std::error::Error + 'static)),
+            LoxError::ParseErrors(errs) => errs
+                .first()
+                .map(|err| err as &(dyn std::error::Error + 'static)),
+            LoxError::RuntimeError(err) => Some(err),
+            LoxError::Io(_, err) => Some(err),
+        }
+    }
+}
+
+#[test]
+fn test_display_errors() {
+    use std::error::Error;
+
+    let err = LoxError::RuntimeError(RuntimeError::new("Undefined variable `a`.".to_string(), 3));
+    assert_eq!(
+        err.to_string(),
+        "RuntimeError: [line 3] Undefined variable `a`."
+    );
+    assert_eq!(
+        err.source().unwrap().to_string(),
+        "[line 3] Undefined variable `a`."
+    );
+
+    let err = LoxError::ParseErrors(vec![
+        ParseError::new("[line 1] first".to_string()),
+        ParseError::new("[line 2] second".to_string()),
+    ]);
+    assert_eq!(
+        err.to_string(),
+        "Error: [line 1] first\nError: [line 2] second"
+    );
+    assert_eq!(err.source().unwrap().to_string(), "[line 1] first");
+    assert_eq!(err.exit_code(), 65);
+
+    // every kind of error shares the `[line N] ` prefix
+    let err = ScanError::new("@".to_string(), 4);
+    assert_eq!(err.to_string(), "[line 4] Invalid token: @");
 }
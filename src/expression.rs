@@ -1,3 +1,8 @@
+use std::{cell::Cell, rc::Rc};
+
+use crate::environment::ScopeRef;
+use crate::native::Native;
+use crate::statement::FunctionStmt;
 use crate::token::{Token, TokenType};
 
 pub enum Expr {
@@ -7,6 +12,22 @@ pub enum Expr {
     Literal(LiteralExpr),
     Variable(VariableExpr),
     Assign(AssignExpr),
+    Postfix(PostfixExpr),
+    Logical(LogicalExpr),
+    Call(CallExpr),
+    Ternary(TernaryExpr),
+    Function(FunctionExpr),
+    List(ListExpr),
+    Map(MapExpr),
+    Index(IndexExpr),
+}
+
+/// `a++` or `a--`, evaluates to the value before the update
+pub struct PostfixExpr {
+    pub var: Token,
+    pub operator: Token,
+    /// scope hops from the use to the declaration, set by the resolver
+    pub depth: Cell<Option<usize>>,
 }
 
 pub struct AssignExpr {
@@ -14,9 +35,62 @@ pub struct AssignExpr {
     pub lvar: Token,
     /// right value expression
     pub value: Box<Expr>,
+    /// scope hops from the use to the declaration, set by the resolver
+    pub depth: Cell<Option<usize>>,
 }
 pub struct VariableExpr {
     pub var: Token,
+    /// scope hops from the use to the declaration, set by the resolver
+    pub depth: Cell<Option<usize>>,
+}
+
+/// `callee(arguments)`
+pub struct CallExpr {
+    pub callee: Box<Expr>,
+    /// the closing `)`, whose line is reported by call errors
+    pub paren: Token,
+    pub arguments: Vec<Expr>,
+}
+
+/// `and` / `or`, the right operand is only evaluated when it decides the result
+pub struct LogicalExpr {
+    pub left: Box<Expr>,
+    pub operator: Token,
+    pub right: Box<Expr>,
+}
+
+/// `condition ? then_branch : else_branch`, only the chosen branch is evaluated
+pub struct TernaryExpr {
+    pub condition: Box<Expr>,
+    pub then_branch: Box<Expr>,
+    pub else_branch: Box<Expr>,
+}
+
+/// `func (params) { body }`, evaluates to a function closing over the current scope
+pub struct FunctionExpr {
+    pub declaration: Rc<FunctionStmt>,
+}
+
+/// `[elements]`, evaluates the elements in order into a new list
+pub struct ListExpr {
+    /// the opening `[`
+    pub bracket: Token,
+    pub elements: Vec<Expr>,
+}
+
+/// `{key: value, ...}`, evaluates the entries in order into a new map
+pub struct MapExpr {
+    /// the opening `{`
+    pub brace: Token,
+    pub entries: Vec<(Expr, Expr)>,
+}
+
+/// `target[index]`
+pub struct IndexExpr {
+    pub target: Box<Expr>,
+    /// the closing `]`, whose line is reported by index errors
+    pub bracket: Token,
+    pub index: Box<Expr>,
 }
 
 pub struct BinaryExpr {
@@ -42,13 +116,19 @@ pub struct LiteralExpr {
 impl LiteralExpr {
     pub fn get_literal_value(&self) -> LiteralValue {
         match self.token.r#type {
-            TokenType::String => LiteralValue::Str(self.token.lexeme.to_owned()),
+            TokenType::String => LiteralValue::Str(self.token.lexeme.as_str().into()),
             TokenType::Number => {
-                let num = self.token.lexeme.parse::<f64>().unwrap();
+                let lexeme = &self.token.lexeme;
+                let num = if lexeme.starts_with("0x") || lexeme.starts_with("0X") {
+                    crate::scanner::parse_hex_float(lexeme).unwrap()
+                } else {
+                    lexeme.parse::<f64>().unwrap()
+                };
                 LiteralValue::Num(num)
             }
             TokenType::True => LiteralValue::Bool(true),
             TokenType::False => LiteralValue::Bool(false),
+            TokenType::Nil => LiteralValue::Nil,
 
             _ => {
                 unreachable!()
@@ -60,87 +140,457 @@ impl LiteralExpr {
 #[derive(Debug, PartialEq, Clone)]
 pub enum LiteralValue {
     Num(f64),
-    Str(String),
+    /// shared, so reading a variable or passing it around never copies the text
+    Str(Rc<str>),
     Bool(bool),
     Nil,
+    Function(Rc<LoxFunction>),
+    NativeFn(Native),
+    /// immutable, so copies of a list share the elements
+    List(Rc<Vec<LiteralValue>>),
+    Map(Rc<LoxMap>),
+}
+
+/// string keys to values in insertion order, so printing and `keys` are deterministic
+#[derive(Debug, Default)]
+pub struct LoxMap {
+    entries: Vec<(Rc<str>, LiteralValue)>,
+}
+
+impl LoxMap {
+    /// a key already present keeps its position and takes the new value
+    pub fn insert(&mut self, key: Rc<str>, value: LiteralValue) {
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&LiteralValue> {
+        self.entries
+            .iter()
+            .find(|(k, _)| &**k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Rc<str>, &LiteralValue)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+/// maps with the same entries are equal whatever order they were inserted in
+impl PartialEq for LoxMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+/// a user function value created by a `func` declaration
+pub struct LoxFunction {
+    pub declaration: Rc<FunctionStmt>,
+    /// the innermost scope at the declaration, kept alive for the body to see
+    pub closure: ScopeRef,
+}
+
+impl LoxFunction {
+    pub fn arity(&self) -> usize {
+        self.declaration.params.len()
+    }
+}
+
+/// functions are only equal to themselves
+impl PartialEq for LoxFunction {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl std::fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.declaration.name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<anonymous fn>"),
+        }
+    }
+}
+
+impl LiteralValue {
+    /// the name of the value's type as users write it
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LiteralValue::Num(_) => "number",
+            LiteralValue::Str(_) => "string",
+            LiteralValue::Bool(_) => "bool",
+            LiteralValue::Nil => "nil",
+            LiteralValue::Function(_) | LiteralValue::NativeFn(_) => "function",
+            LiteralValue::List(_) => "list",
+            LiteralValue::Map(_) => "map",
+        }
+    }
+
+    /// how the value shows inside a collection, strings are quoted there
+    fn fmt_element(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LiteralValue::Str(str) => write!(f, "\"{}\"", str),
+            _ => write!(f, "{}", self),
+        }
+    }
+
+    /// the `{:#}` form, one element per line indented two spaces a level
+    fn fmt_pretty(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
+        let indent = "  ".repeat(depth + 1);
+        match self {
+            LiteralValue::List(elements) if !elements.is_empty() => {
+                writeln!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    write!(f, "{}", indent)?;
+                    element.fmt_pretty(f, depth + 1)?;
+                    writeln!(f, "{}", if i + 1 < elements.len() { "," } else { "" })?;
+                }
+                write!(f, "{}]", &indent[2..])
+            }
+            LiteralValue::Map(map) if !map.is_empty() => {
+                writeln!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    write!(f, "{}\"{}\": ", indent, key)?;
+                    value.fmt_pretty(f, depth + 1)?;
+                    writeln!(f, "{}", if i + 1 < map.len() { "," } else { "" })?;
+                }
+                write!(f, "{}}}", &indent[2..])
+            }
+            _ => self.fmt_element(f),
+        }
+    }
 }
 
 impl std::fmt::Display for LiteralValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            LiteralValue::List(_) | LiteralValue::Map(_) if f.alternate() => self.fmt_pretty(f, 0),
+            // whole numbers never show a fraction, the rest print with full precision
+            LiteralValue::Num(num) if num.fract() == 0.0 && num.is_finite() => {
+                write!(f, "{:.0}", num)
+            }
             LiteralValue::Num(num) => write!(f, "{}", num),
             LiteralValue::Str(str) => write!(f, "{}", str),
             LiteralValue::Bool(b) => write!(f, "{}", b),
             LiteralValue::Nil => write!(f, "nil"),
+            LiteralValue::Function(function) => function.fmt(f),
+            LiteralValue::NativeFn(native) => native.fmt(f),
+            LiteralValue::List(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_element(f)?;
+                }
+                write!(f, "]")
+            }
+            LiteralValue::Map(map) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "\"{}\": ", key)?;
+                    value.fmt_element(f)?;
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
 
-impl ToString for Expr {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Expr::Binary(binary) => binary.to_string(),
-            Expr::Unary(unary) => unary.to_string(),
-            Expr::Grouping(grouping) => grouping.to_string(),
-            Expr::Literal(literal) => literal.to_string(),
-            Expr::Variable(var) => var.to_string(),
-            Expr::Assign(assign) => assign.to_string(),
+            Expr::Binary(binary) => binary.fmt(f),
+            Expr::Unary(unary) => unary.fmt(f),
+            Expr::Grouping(grouping) => grouping.fmt(f),
+            Expr::Literal(literal) => literal.fmt(f),
+            Expr::Variable(var) => var.fmt(f),
+            Expr::Assign(assign) => assign.fmt(f),
+            Expr::Postfix(postfix) => postfix.fmt(f),
+            Expr::Logical(logical) => logical.fmt(f),
+            Expr::Call(call) => call.fmt(f),
+            Expr::Ternary(ternary) => ternary.fmt(f),
+            Expr::Function(function) => function.declaration.fmt(f),
+            Expr::List(list) => list.fmt(f),
+            Expr::Map(map) => map.fmt(f),
+            Expr::Index(index) => index.fmt(f),
         }
     }
 }
 
-impl ToString for UnaryExpr {
-    fn to_string(&self) -> String {
-        format!(
-            "({} {})",
-            self.operator.lexeme.clone(),
-            self.expression.to_string()
-        )
+impl std::fmt::Display for UnaryExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({} {})", self.operator.lexeme, self.expression)
+    }
+}
+
+impl std::fmt::Display for BinaryExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({} {} {})", self.operator.lexeme, self.left, self.right)
+    }
+}
+
+impl std::fmt::Display for CallExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(call {}", self.callee)?;
+        for argument in &self.arguments {
+            write!(f, " {}", argument)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Display for ListExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(list")?;
+        for element in &self.elements {
+            write!(f, " {}", element)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Display for MapExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(map")?;
+        for (key, value) in &self.entries {
+            write!(f, " {} {}", key, value)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Display for IndexExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(index {} {})", self.target, self.index)
     }
 }
 
-impl ToString for BinaryExpr {
-    fn to_string(&self) -> String {
-        format!(
-            "({} {} {})",
-            self.operator.lexeme.clone(),
-            self.left.to_string(),
-            self.right.to_string()
+impl std::fmt::Display for LogicalExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({} {} {})", self.operator.lexeme, self.left, self.right)
+    }
+}
+
+impl std::fmt::Display for TernaryExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "(? {} {} {})",
+            self.condition, self.then_branch, self.else_branch
         )
     }
 }
 
-impl ToString for GroupingExpr {
-    fn to_string(&self) -> String {
-        format!("(grouping {})", self.expression.to_string())
+impl std::fmt::Display for GroupingExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(grouping {})", self.expression)
     }
 }
 
-impl ToString for LiteralExpr {
-    fn to_string(&self) -> String {
-        self.token.lexeme.clone()
+impl std::fmt::Display for LiteralExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.token.lexeme)
     }
 }
 
-impl ToString for VariableExpr {
-    fn to_string(&self) -> String {
-        self.var.lexeme.clone()
+impl std::fmt::Display for VariableExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.var.lexeme)
     }
 }
-impl ToString for AssignExpr {
-    fn to_string(&self) -> String {
-        format!("{} = {}", self.lvar.lexeme.clone(), self.value.to_string())
+impl std::fmt::Display for PostfixExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(post{} {})", self.operator.lexeme, self.var.lexeme)
     }
 }
+
+impl std::fmt::Display for AssignExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.lvar.lexeme, self.value)
+    }
+}
+/// binding power of each level of the grammar, loosest first
+mod precedence {
+    pub const ASSIGNMENT: u8 = 1;
+    pub const TERNARY: u8 = 2;
+    pub const OR: u8 = 3;
+    pub const AND: u8 = 4;
+    pub const EQUALITY: u8 = 5;
+    pub const COMPARISON: u8 = 6;
+    pub const TERM: u8 = 7;
+    pub const FACTOR: u8 = 8;
+    pub const UNARY: u8 = 9;
+    pub const POSTFIX: u8 = 10;
+}
+
+impl Expr {
+    /// the line of the token that best locates the expression, used by runtime errors
+    pub fn line(&self) -> usize {
+        match self {
+            Expr::Binary(binary) => binary.operator.line,
+            Expr::Unary(unary) => unary.operator.line,
+            Expr::Grouping(grouping) => grouping.expression.line(),
+            Expr::Literal(literal) => literal.token.line,
+            Expr::Variable(var) => var.var.line,
+            Expr::Assign(assign) => assign.lvar.line,
+            Expr::Postfix(postfix) => postfix.operator.line,
+            Expr::Logical(logical) => logical.operator.line,
+            Expr::Call(call) => call.paren.line,
+            Expr::Ternary(ternary) => ternary.condition.line(),
+            Expr::Function(function) => function.declaration.line,
+            Expr::List(list) => list.bracket.line,
+            Expr::Map(map) => map.brace.line,
+            Expr::Index(index) => index.bracket.line,
+        }
+    }
+
+    /// render back in infix notation with only the parentheses the precedence needs,
+    /// e.g. `(* (+ 1 2) 3)` renders as `(1 + 2) * 3`
+    pub fn to_infix_string(&self) -> String {
+        self.infix(0)
+    }
+
+    /// render as an operand of a `min_prec` operator, parenthesized if it binds looser
+    fn infix(&self, min_prec: u8) -> String {
+        let (prec, rendered) = match self {
+            Expr::Binary(binary) => {
+                let prec = match binary.operator.r#type {
+                    TokenType::EqualEqual | TokenType::BangEqual => precedence::EQUALITY,
+                    TokenType::Greater
+                    | TokenType::GreaterEqual
+                    | TokenType::Less
+                    | TokenType::LessEqual => precedence::COMPARISON,
+                    TokenType::Plus | TokenType::Minus => precedence::TERM,
+                    _ => precedence::FACTOR,
+                };
+                // left associative, so a right operand of the same level needs parentheses
+                let rendered = format!(
+                    "{} {} {}",
+                    binary.left.infix(prec),
+                    binary.operator.lexeme,
+                    binary.right.infix(prec + 1)
+                );
+                (prec, rendered)
+            }
+            Expr::Logical(logical) => {
+                let prec = match logical.operator.r#type {
+                    TokenType::Or => precedence::OR,
+                    _ => precedence::AND,
+                };
+                let rendered = format!(
+                    "{} {} {}",
+                    logical.left.infix(prec),
+                    logical.operator.lexeme,
+                    logical.right.infix(prec + 1)
+                );
+                (prec, rendered)
+            }
+            Expr::Unary(unary) => (
+                precedence::UNARY,
+                format!(
+                    "{}{}",
+                    unary.operator.lexeme,
+                    unary.expression.infix(precedence::UNARY)
+                ),
+            ),
+            // explicit parentheses are re-derived from precedence
+            Expr::Grouping(grouping) => return grouping.expression.infix(min_prec),
+            Expr::Literal(literal) => match literal.token.r#type {
+                TokenType::String => (u8::MAX, format!("\"{}\"", literal.token.lexeme)),
+                _ => (u8::MAX, literal.token.lexeme.clone()),
+            },
+            Expr::Variable(var) => (u8::MAX, var.var.lexeme.clone()),
+            Expr::Call(call) => {
+                let arguments: Vec<String> =
+                    call.arguments.iter().map(|arg| arg.infix(0)).collect();
+                let rendered = format!("{}({})", call.callee.infix(u8::MAX), arguments.join(", "));
+                (u8::MAX, rendered)
+            }
+            Expr::Assign(assign) => (
+                precedence::ASSIGNMENT,
+                format!(
+                    "{} = {}",
+                    assign.lvar.lexeme,
+                    assign.value.infix(precedence::ASSIGNMENT)
+                ),
+            ),
+            Expr::Postfix(postfix) => (
+                precedence::POSTFIX,
+                format!("{}{}", postfix.var.lexeme, postfix.operator.lexeme),
+            ),
+            Expr::List(list) => {
+                let elements: Vec<String> = list
+                    .elements
+                    .iter()
+                    .map(|element| element.infix(0))
+                    .collect();
+                (u8::MAX, format!("[{}]", elements.join(", ")))
+            }
+            Expr::Map(map) => {
+                let entries: Vec<String> = map
+                    .entries
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key.infix(0), value.infix(0)))
+                    .collect();
+                (u8::MAX, format!("{{{}}}", entries.join(", ")))
+            }
+            Expr::Index(index) => (
+                u8::MAX,
+                format!("{}[{}]", index.target.infix(u8::MAX), index.index.infix(0)),
+            ),
+            // the body has no infix form
+            Expr::Function(function) => (u8::MAX, function.declaration.to_string()),
+            // right associative, so only a nested condition needs parentheses
+            Expr::Ternary(ternary) => (
+                precedence::TERNARY,
+                format!(
+                    "{} ? {} : {}",
+                    ternary.condition.infix(precedence::TERNARY + 1),
+                    ternary.then_branch.infix(0),
+                    ternary.else_branch.infix(precedence::TERNARY)
+                ),
+            ),
+        };
+        if prec < min_prec {
+            format!("({})", rendered)
+        } else {
+            rendered
+        }
+    }
+}
+
 #[test]
 fn expression_to_string() {
     let literal_114 = LiteralExpr {
-        token: Token::new(crate::token::TokenType::Number, "114".to_string(), 1),
+        token: Token::new(crate::token::TokenType::Number, "114".to_string(), 1, 1),
     };
     let literal_514 = LiteralExpr {
-        token: Token::new(crate::token::TokenType::Number, "514".to_string(), 1),
+        token: Token::new(crate::token::TokenType::Number, "514".to_string(), 1, 1),
     };
-    let token_plus = Token::new(crate::token::TokenType::Plus, "+".to_string(), 1);
+    let token_plus = Token::new(crate::token::TokenType::Plus, "+".to_string(), 1, 1);
 
     //    +
     //  /   \
@@ -158,7 +608,7 @@ fn expression_to_string() {
     let unary = Expr::Unary(UnaryExpr {
         operator: token_plus.clone(),
         expression: Box::new(Expr::Literal(LiteralExpr {
-            token: Token::new(crate::token::TokenType::Number, "514".to_string(), 1),
+            token: Token::new(crate::token::TokenType::Number, "514".to_string(), 1, 1),
         })),
     });
 
@@ -170,7 +620,41 @@ fn expression_to_string() {
     assert_eq!(complicated.to_string(), "(+ (+ 114 514) (+ 514))")
 }
 
-pub enum Value {
-    Literal(LiteralValue),
-    Variable(),
+#[test]
+fn expression_to_infix_string() {
+    let data = vec![
+        ("1 + 2 * 3", "1 + 2 * 3"),
+        ("(1 + 2) * 3", "(1 + 2) * 3"),
+        ("((1)) + (2 * 3)", "1 + 2 * 3"),
+        ("1 - (2 - 3)", "1 - (2 - 3)"),
+        ("(1 - 2) - 3", "1 - 2 - 3"),
+        ("-(1 + 2) < 3 == true", "-(1 + 2) < 3 == true"),
+        ("a = (b = 1 + 2)", "a = b = 1 + 2"),
+        ("(a = 1) + 2", "(a = 1) + 2"),
+        ("\"a\" + \"b\"", "\"a\" + \"b\""),
+        ("-(a++)", "-a++"),
+        ("!(!a)", "!!a"),
+        ("(a or b) and c", "(a or b) and c"),
+        ("a or (b and c)", "a or b and c"),
+        ("f(1 + 2, (g))(3)", "f(1 + 2, g)(3)"),
+        ("a ? b : (c ? d : e)", "a ? b : c ? d : e"),
+        ("(a ? b : c) ? d : e", "(a ? b : c) ? d : e"),
+        ("x = (a or b) ? 1 : 2", "x = a or b ? 1 : 2"),
+        ("[1 + 2, [(a)]][0]", "[1 + 2, [a]][0]"),
+        ("(-a)[1]", "(-a)[1]"),
+        (
+            "{\"a\": (1), \"b\": {}}[\"a\"]",
+            "{\"a\": 1, \"b\": {}}[\"a\"]",
+        ),
+    ];
+
+    for (input, should_be) in data {
+        let tokens = crate::scanner::Scanner::new(input.to_string())
+            .scan_tokens()
+            .unwrap();
+        let expr = crate::parser::Parser::new(tokens)
+            .parse_expression()
+            .unwrap();
+        assert_eq!(expr.to_infix_string(), should_be);
+    }
 }
@@ -1,70 +1,342 @@
+use std::{io::Write, rc::Rc};
+
 use crate::{
     environment::Environment,
-    error::RuntimeError,
-    expression::{self, BinaryExpr, Expr, LiteralExpr, LiteralValue, UnaryExpr},
+    error::{LoxError, ParseError, RuntimeError},
+    expression::{
+        BinaryExpr, CallExpr, Expr, IndexExpr, ListExpr, LiteralValue, LogicalExpr, LoxFunction,
+        LoxMap, MapExpr, PostfixExpr, TernaryExpr, UnaryExpr,
+    },
+    native::Native,
     parser::Parser,
     scanner::Scanner,
-    statement::Stmt,
-    token::TokenType,
+    statement::{Block, FunctionStmt, Stmt, TryStmt},
+    token::{Token, TokenType},
 };
 
+/// default upper bound of the count in `string * count`
+pub const DEFAULT_MAX_STRING_REPEAT: usize = 1 << 20;
+
+/// buffered prints are written out once they reach this many bytes
+const PRINT_BUFFER_CAPACITY: usize = 8 * 1024;
+
+/// looks up an environment variable by name
+pub type EnvSource = Box<dyn Fn(&str) -> Option<String>>;
+
+/// default upper bound of nested function calls
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 200;
+
+/// structured events for embedders that render output themselves,
+/// emitted alongside the text written to the output
+pub trait EventSink {
+    /// a `print` outside of any `capture` block, `text` has no trailing newline
+    fn on_print(&mut self, _text: &str, _line: usize) {}
+    /// a runtime error of a top-level statement
+    fn on_error(&mut self, _err: &RuntimeError) {}
+}
+
+/// the time source of `clock`, `sleep` and `time`, swapped out to keep tests deterministic
+pub trait Clock {
+    /// seconds since the Unix epoch
+    fn now(&mut self) -> f64;
+    fn sleep(&mut self, seconds: f64);
+}
+
+/// the real time of the process
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&mut self) -> f64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0.0, |since_epoch| since_epoch.as_secs_f64())
+    }
+
+    fn sleep(&mut self, seconds: f64) {
+        std::thread::sleep(std::time::Duration::from_secs_f64(seconds));
+    }
+}
+
+/// an `EventSink` writing prints to `W` like the interpreter output does
+pub struct WriteSink<W>(pub W);
+
+impl<W: std::io::Write> EventSink for WriteSink<W> {
+    fn on_print(&mut self, text: &str, _line: usize) {
+        writeln!(self.0, "{}", text).unwrap();
+    }
+}
+
 pub struct Interpreter<W> {
     environment: Environment,
     output: W,
+    /// buffers of the enclosing `capture` blocks, the innermost is last
+    captures: Vec<String>,
+    /// the largest count allowed in `string * count`, to prevent memory blowup
+    pub max_string_repeat: usize,
+    /// keep running the next top-level statement after a runtime error,
+    /// the state may be inconsistent so it's only meant for linting
+    pub continue_on_error: bool,
+    /// print booleans as `1`/`0` instead of `true`/`false`
+    pub bool_as_int_output: bool,
+    /// runtime errors collected under `continue_on_error`
+    errors: Vec<RuntimeError>,
+    /// the value of an executed `return`, statements are skipped until the call takes it
+    returning: Option<LiteralValue>,
+    /// the number of function calls in progress
+    call_depth: usize,
+    /// the most nested calls allowed, so runaway recursion fails cleanly
+    pub max_call_depth: usize,
+    /// where `env(name)` looks variables up, the process environment by default
+    pub env_source: EnvSource,
+    /// let `write_file` touch the filesystem, off so embedders are sandboxed by default
+    pub allow_fs_write: bool,
+    /// let `read_file` read the filesystem, off by default like `allow_fs_write`
+    pub allow_fs_read: bool,
+    /// collect prints and write them out in large chunks instead of flushing every line,
+    /// everything is flushed before `interpret` returns; 200k prints to a file run
+    /// about twice as fast this way
+    pub buffered_output: bool,
+    /// prints not yet written under `buffered_output`
+    print_buffer: Vec<u8>,
+    /// receives print and error events if set
+    pub event_sink: Option<Box<dyn EventSink>>,
+    /// write a line to `trace_output` for every statement executed and every call
+    pub trace: bool,
+    /// where `trace` lines go, stderr by default
+    pub trace_output: Box<dyn std::io::Write>,
+    /// what `clock`, `sleep` and `time` read, the system clock by default
+    pub clock: Box<dyn Clock>,
 }
 
 impl<W: std::io::Write> Interpreter<W> {
     pub fn new(output: W) -> Self {
+        let mut environment = Environment::new();
+        for native in Native::ALL {
+            environment
+                .define(native.name(), LiteralValue::NativeFn(*native), 0)
+                .expect("native names are valid");
+        }
         Self {
-            environment: Environment::new(),
+            environment,
             output,
+            captures: vec![],
+            max_string_repeat: DEFAULT_MAX_STRING_REPEAT,
+            continue_on_error: false,
+            bool_as_int_output: false,
+            errors: vec![],
+            returning: None,
+            call_depth: 0,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            env_source: Box::new(|name| std::env::var(name).ok()),
+            allow_fs_write: false,
+            allow_fs_read: false,
+            buffered_output: false,
+            print_buffer: vec![],
+            event_sink: None,
+            trace: false,
+            trace_output: Box::new(std::io::stderr()),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// configure an interpreter through chained setters, the rest keeps the defaults of `new`
+    pub fn builder(output: W) -> InterpreterBuilder<W> {
+        InterpreterBuilder {
+            interpreter: Self::new(output),
         }
     }
 
     /// Interpret a program (contains multiple statements)
-    pub fn interpret(&self, stmts: &Vec<Stmt>) -> Result<(), RuntimeError> {
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+        let mut result = Ok(());
+        for stmt in stmts {
+            if let Err(err) = self.execute(stmt) {
+                if let Some(sink) = self.event_sink.as_mut() {
+                    sink.on_error(&err);
+                }
+                if !self.continue_on_error || err.is_fatal() {
+                    result = Err(err);
+                    break;
+                }
+                self.errors.push(err);
+            }
+        }
+        self.flush_output();
+        result
+    }
+
+    /// write out the prints held back by `buffered_output` and flush the writer
+    pub fn flush_output(&mut self) {
+        if !self.print_buffer.is_empty() {
+            self.output.write_all(&self.print_buffer).unwrap();
+            self.print_buffer.clear();
+        }
+        self.output.flush().unwrap();
+    }
+
+    /// scan, parse and evaluate one expression against the current environment
+    pub fn eval_str(&mut self, src: &str) -> Result<LiteralValue, LoxError> {
+        let tokens = Scanner::new(src.to_string())
+            .scan_tokens()
+            .map_err(LoxError::ScanErrors)?;
+        let mut parser = Parser::new(tokens);
+        let expr = parser
+            .parse_expression()
+            .map_err(|err| LoxError::ParseErrors(vec![err]))?;
+        if !parser.all_parsed() {
+            return Err(LoxError::ParseErrors(vec![ParseError::new(
+                "not all token parsed".to_string(),
+            )]));
+        }
+        let value = self.evaluate(&expr).map_err(LoxError::RuntimeError)?;
+        value.ok_or_else(|| {
+            LoxError::RuntimeError(RuntimeError::new(
+                format!("Expression `{}` has no value.", expr),
+                expr.line(),
+            ))
+        })
+    }
+
+    /// the names of the globals defined so far, natives included
+    pub fn global_names(&self) -> Vec<String> {
+        self.environment.global_names()
+    }
+
+    /// take the runtime errors collected under `continue_on_error`
+    pub fn take_errors(&mut self) -> Vec<RuntimeError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// execute statements in order, stopping at the first error or `return`
+    fn execute_block(&mut self, block: &Block) -> Result<(), RuntimeError> {
+        self.environment.create_scope(block.line)?;
+        let result = self.execute_stmts(&block.stmts);
+        // drop the scope even when a statement failed
+        self.environment.drop_scope();
+        result
+    }
+
+    /// a `panic` skips both clauses, a `return` in `finally` replaces the pending outcome
+    fn execute_try(&mut self, try_stmt: &TryStmt) -> Result<(), RuntimeError> {
+        let mut result = self.execute_block(&try_stmt.body);
+        if let Err(err) = &result {
+            if err.is_fatal() {
+                return result;
+            }
+            if let Some(catch) = &try_stmt.catch {
+                let message = LiteralValue::Str(err.message().into());
+                self.environment.create_scope(catch.body.line)?;
+                result = self
+                    .environment
+                    .define(&catch.var_name, message, catch.body.line)
+                    .and_then(|_| self.execute_block(&catch.body));
+                self.environment.drop_scope();
+            }
+        }
+        let Some(finally) = &try_stmt.finally else {
+            return result;
+        };
+        if matches!(&result, Err(err) if err.is_fatal()) {
+            return result;
+        }
+        // the finally block runs to its end even while a `return` is pending
+        let pending_return = self.returning.take();
+        self.execute_block(finally)?;
+        if self.returning.is_some() {
+            return Ok(());
+        }
+        self.returning = pending_return;
+        result
+    }
+
+    fn execute_stmts(&mut self, stmts: &[Stmt]) -> Result<(), RuntimeError> {
+        for stmt in stmts {
+            self.execute(stmt)?;
+            if self.returning.is_some() {
+                break;
+            }
+        }
         Ok(())
     }
 
     pub fn evaluate(&mut self, expr: &Expr) -> Result<Option<LiteralValue>, RuntimeError> {
         match expr {
-            Expr::Binary(binary) => self.evaluate_binary(binary).map(|value| Some(value)),
-            Expr::Unary(unary) => self.evaluate_unary(unary).map(|value| Some(value)),
+            Expr::Binary(binary) => self.evaluate_binary(binary).map(Some),
+            Expr::Unary(unary) => self.evaluate_unary(unary).map(Some),
             Expr::Grouping(grouping) => self.evaluate(&grouping.expression),
             Expr::Literal(literal) => Ok(Some(literal.get_literal_value())),
             Expr::Variable(var) => {
-                // TODO: optimization needed here
-                let v = self.environment.get(&var.var.lexeme)?;
-                Ok(Some(v.clone()))
+                let v = self.look_up(&var.var, var.depth.get())?;
+                Ok(Some(v))
             }
+            Expr::Postfix(postfix) => self.evaluate_postfix(postfix).map(Some),
+            Expr::Logical(logical) => self.evaluate_logical(logical).map(Some),
+            Expr::Call(call) => self.evaluate_call(call).map(Some),
+            Expr::Ternary(ternary) => self.evaluate_ternary(ternary).map(Some),
+            Expr::Function(function) => Ok(Some(self.make_function(&function.declaration))),
+            Expr::List(list) => self.evaluate_list(list).map(Some),
+            Expr::Map(map) => self.evaluate_map(map).map(Some),
+            Expr::Index(index) => self.evaluate_index(index).map(Some),
             Expr::Assign(assign) => {
                 let value = self.evaluate(&assign.value)?;
                 match value {
                     Some(value) => {
-                        self.environment
-                            .assign(assign.lvar.clone(), value.clone())?;
+                        self.assign_variable(
+                            assign.lvar.clone(),
+                            assign.depth.get(),
+                            value.clone(),
+                        )?;
                         Ok(Some(value))
                     }
-                    None => Err(RuntimeError::new(format!(
-                        "Expression `{}` has no value.",
-                        assign.value.to_string()
-                    ))),
+                    None => Err(RuntimeError::new(
+                        format!("Expression `{}` has no value.", assign.value),
+                        assign.lvar.line,
+                    )),
                 }
             }
         }
     }
 
     pub fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+        if self.trace {
+            self.trace_line(format_args!("exec {}", stmt));
+        }
         match stmt {
             Stmt::Print(stmt) => {
                 let value = self.evaluate(&stmt.expr)?;
                 match value {
-                    None => Err(RuntimeError::new(format!(
-                        "Expression {} has no value and cannot be printed!",
-                        stmt.expr.to_string()
-                    ))),
+                    None => Err(RuntimeError::new(
+                        format!(
+                            "Expression {} has no value and cannot be printed!",
+                            stmt.expr
+                        ),
+                        stmt.expr.line(),
+                    )),
                     Some(v) => {
-                        writeln!(self.output, "{}", format!("{}", v)).unwrap();
+                        let text = match v {
+                            LiteralValue::Bool(b) if self.bool_as_int_output => {
+                                (b as u8).to_string()
+                            }
+                            v if stmt.pretty => format!("{:#}", v),
+                            v => v.to_string(),
+                        };
+                        if let Some(capture) = self.captures.last_mut() {
+                            capture.push_str(&format!("{}\n", text));
+                            return Ok(());
+                        }
+                        if let Some(sink) = self.event_sink.as_mut() {
+                            sink.on_print(&text, stmt.expr.line());
+                        }
+                        if self.buffered_output {
+                            writeln!(self.print_buffer, "{}", text).unwrap();
+                            if self.print_buffer.len() >= PRINT_BUFFER_CAPACITY {
+                                self.flush_output();
+                            }
+                            return Ok(());
+                        }
+                        writeln!(self.output, "{}", text).unwrap();
+                        // keep REPL output in order with the prompt
+                        self.output.flush().unwrap();
                         Ok(())
                     }
                 }
@@ -74,36 +346,83 @@ impl<W: std::io::Write> Interpreter<W> {
                 Ok(())
             }
             Stmt::Var(var_stmt) => {
-                if let Some(init_v) = &var_stmt.initializer {
-                    if let Some(lit_v) = self.evaluate(init_v)? {
-                        self.environment.define(var_stmt.var_name.as_str(), lit_v);
-                        Ok(())
-                    } else {
+                let value = match &var_stmt.initializer {
+                    Some(init_v) => self.evaluate(init_v)?.ok_or_else(|| {
                         // no value
-                        return Err(RuntimeError::new(format!(
-                            "Expression `{}` has no value.",
-                            init_v.to_string()
-                        )));
+                        RuntimeError::new(
+                            format!("Expression `{}` has no value.", init_v),
+                            init_v.line(),
+                        )
+                    })?,
+                    // no initializer
+                    None => LiteralValue::Nil,
+                };
+                let name = var_stmt.var_name.as_str();
+                match var_stmt.annotation {
+                    Some(annotation) => {
+                        self.environment
+                            .define_typed(name, value, annotation, var_stmt.line)
                     }
+                    None => self.environment.define(name, value, var_stmt.line),
+                }
+            }
+            Stmt::Block(block) => self.execute_block(block),
+            Stmt::Try(try_stmt) => self.execute_try(try_stmt),
+            Stmt::If(if_stmt) => {
+                if self.evaluate_condition(&if_stmt.condition)? {
+                    self.execute(&if_stmt.then_branch)
+                } else if let Some(else_branch) = &if_stmt.else_branch {
+                    self.execute(else_branch)
                 } else {
-                    // no initializer
-                    self.environment
-                        .define(var_stmt.var_name.as_str(), LiteralValue::Nil);
                     Ok(())
                 }
             }
-            Stmt::Block(block) => {
-                self.environment.create_scope();
-
-                let stmts = &block.stmts;
-                for stmt in stmts {
-                    // recover from current environment
-                    self.execute(stmt)?;
+            Stmt::While(while_stmt) => {
+                while self.evaluate_condition(&while_stmt.condition)? {
+                    self.execute(&while_stmt.body)?;
+                    if self.returning.is_some() {
+                        break;
+                    }
                 }
-
-                self.environment.drop_scope();
                 Ok(())
             }
+            Stmt::Function(declaration) => {
+                let function = self.make_function(declaration);
+                self.environment
+                    .define(declaration.display_name(), function, declaration.line)
+            }
+            Stmt::Return(return_stmt) => {
+                if self.call_depth == 0 {
+                    return Err(RuntimeError::new(
+                        "Cannot return outside of a function.".to_string(),
+                        return_stmt.line,
+                    ));
+                }
+                let value = match &return_stmt.value {
+                    Some(value) => self.evaluate(value)?.ok_or_else(|| {
+                        RuntimeError::new(
+                            format!("Expression `{}` has no value.", value),
+                            value.line(),
+                        )
+                    })?,
+                    None => LiteralValue::Nil,
+                };
+                self.returning = Some(value);
+                Ok(())
+            }
+            Stmt::Capture(capture) => {
+                self.environment.create_scope(capture.line)?;
+                self.captures.push(String::new());
+                let result = self.execute_stmts(&capture.body.stmts);
+                self.environment.drop_scope();
+                let captured = self.captures.pop().expect("capture buffer must exist");
+                result?;
+                self.environment.define(
+                    &capture.var_name,
+                    LiteralValue::Str(captured.into()),
+                    capture.line,
+                )
+            }
         }
     }
 }
@@ -116,40 +435,606 @@ impl<W: std::io::Write> Interpreter<W> {
                     if let LiteralValue::Num(num) = right {
                         Ok(LiteralValue::Num(-num))
                     } else {
-                        Err(RuntimeError::new(format!(
-                            "Operand must be number, not `{:?}`",
-                            right
-                        )))
+                        Err(RuntimeError::new(
+                            format!("Operand must be a number, not a {}.", right.type_name()),
+                            expr.operator.line,
+                        ))
                     }
                 }
                 TokenType::Bang => {
                     let truthy = self.is_truthy(&right);
                     Ok(LiteralValue::Bool(!truthy))
                 }
-                _ => Err(RuntimeError::new(format!(
-                    "Invalid unary operator `{}`",
-                    expr.operator.lexeme
-                ))),
+                _ => Err(RuntimeError::new(
+                    format!("Invalid unary operator `{}`", expr.operator.lexeme),
+                    expr.operator.line,
+                )),
             }
         } else {
-            Err(RuntimeError::new(format!(
-                "Expression {} has no value.",
-                expr.expression.to_string()
-            )))
+            Err(RuntimeError::new(
+                format!("Expression {} has no value.", expr.expression),
+                expr.operator.line,
+            ))
+        }
+    }
+
+    /// whether the condition of an `if`/`while` holds
+    fn evaluate_condition(&mut self, condition: &Expr) -> Result<bool, RuntimeError> {
+        let value = self.evaluate(condition)?.ok_or_else(|| {
+            RuntimeError::new(
+                format!("Expression `{}` has no value.", condition),
+                condition.line(),
+            )
+        })?;
+        Ok(self.is_truthy(&value))
+    }
+
+    fn evaluate_call(&mut self, expr: &CallExpr) -> Result<LiteralValue, RuntimeError> {
+        let line = expr.paren.line;
+        let callee = self.evaluate(&expr.callee)?.ok_or_else(|| {
+            RuntimeError::new(format!("Expression `{}` has no value.", expr.callee), line)
+        })?;
+        let mut arguments = Vec::with_capacity(expr.arguments.len());
+        for argument in &expr.arguments {
+            arguments.push(self.evaluate(argument)?.ok_or_else(|| {
+                RuntimeError::new(
+                    format!("Expression `{}` has no value.", argument),
+                    argument.line(),
+                )
+            })?);
+        }
+        self.call(callee, arguments, line)
+    }
+
+    fn evaluate_list(&mut self, expr: &ListExpr) -> Result<LiteralValue, RuntimeError> {
+        let mut elements = Vec::with_capacity(expr.elements.len());
+        for element in &expr.elements {
+            elements.push(self.evaluate(element)?.ok_or_else(|| {
+                RuntimeError::new(
+                    format!("Expression `{}` has no value.", element),
+                    element.line(),
+                )
+            })?);
+        }
+        Ok(LiteralValue::List(Rc::new(elements)))
+    }
+
+    fn evaluate_map(&mut self, expr: &MapExpr) -> Result<LiteralValue, RuntimeError> {
+        let mut map = LoxMap::default();
+        for (key, value) in &expr.entries {
+            let key = match self.evaluate(key)? {
+                Some(LiteralValue::Str(key)) => key,
+                Some(other) => {
+                    return Err(RuntimeError::new(
+                        format!("Map keys must be strings, not a {}.", other.type_name()),
+                        key.line(),
+                    ))
+                }
+                None => {
+                    return Err(RuntimeError::new(
+                        format!("Expression `{}` has no value.", key),
+                        key.line(),
+                    ))
+                }
+            };
+            let value = self.evaluate(value)?.ok_or_else(|| {
+                RuntimeError::new(
+                    format!("Expression `{}` has no value.", value),
+                    value.line(),
+                )
+            })?;
+            map.insert(key, value);
+        }
+        Ok(LiteralValue::Map(Rc::new(map)))
+    }
+
+    fn evaluate_index(&mut self, expr: &IndexExpr) -> Result<LiteralValue, RuntimeError> {
+        let line = expr.bracket.line;
+        let target = self.evaluate(&expr.target)?.ok_or_else(|| {
+            RuntimeError::new(format!("Expression `{}` has no value.", expr.target), line)
+        })?;
+        let index = self.evaluate(&expr.index)?.ok_or_else(|| {
+            RuntimeError::new(format!("Expression `{}` has no value.", expr.index), line)
+        })?;
+        match (&target, &index) {
+            (LiteralValue::List(elements), LiteralValue::Num(num)) => {
+                if num.fract() != 0.0 {
+                    return Err(RuntimeError::new(
+                        format!("List index must be a whole number, not `{}`.", index),
+                        line,
+                    ));
+                }
+                // negative and too large indices both miss
+                let element = (*num >= 0.0).then(|| elements.get(*num as usize)).flatten();
+                element.cloned().ok_or_else(|| {
+                    RuntimeError::new(
+                        format!(
+                            "Index {} is out of range for a list of length {}.",
+                            index,
+                            elements.len()
+                        ),
+                        line,
+                    )
+                })
+            }
+            (LiteralValue::List(_), _) => Err(RuntimeError::new(
+                format!("List index must be a number, not a {}.", index.type_name()),
+                line,
+            )),
+            (LiteralValue::Map(map), LiteralValue::Str(key)) => {
+                map.get(key).cloned().ok_or_else(|| {
+                    RuntimeError::new(format!("Key \"{}\" is not in the map.", key), line)
+                })
+            }
+            (LiteralValue::Map(_), _) => Err(RuntimeError::new(
+                format!("Map keys must be strings, not a {}.", index.type_name()),
+                line,
+            )),
+            _ => Err(RuntimeError::new(
+                format!(
+                    "Can only index lists and maps, not a {}.",
+                    target.type_name()
+                ),
+                line,
+            )),
+        }
+    }
+
+    /// call a function or native value, checking the number of arguments first
+    fn call(
+        &mut self,
+        callee: LiteralValue,
+        arguments: Vec<LiteralValue>,
+        line: usize,
+    ) -> Result<LiteralValue, RuntimeError> {
+        let arity = match &callee {
+            LiteralValue::Function(function) => function.arity()..=function.arity(),
+            LiteralValue::NativeFn(native) => native.arity(),
+            _ => {
+                return Err(RuntimeError::new(
+                    format!("Can only call functions, not a {}.", callee.type_name()),
+                    line,
+                ))
+            }
+        };
+        if !arity.contains(&arguments.len()) {
+            let expected = if arity.start() == arity.end() {
+                arity.start().to_string()
+            } else {
+                format!("{} to {}", arity.start(), arity.end())
+            };
+            return Err(RuntimeError::new(
+                format!(
+                    "Expected {} arguments but got {}.",
+                    expected,
+                    arguments.len()
+                ),
+                line,
+            ));
+        }
+        match callee {
+            LiteralValue::NativeFn(native) => self.call_native(native, arguments, line),
+            LiteralValue::Function(function) => self.call_function(&function, arguments, line),
+            _ => unreachable!(),
+        }
+    }
+
+    /// the arity is already checked, `line` is the line of the call
+    fn call_native(
+        &mut self,
+        native: Native,
+        arguments: Vec<LiteralValue>,
+        line: usize,
+    ) -> Result<LiteralValue, RuntimeError> {
+        match native {
+            Native::Env => {
+                let name = expect_str(native, &arguments[0], line)?;
+                Ok((self.env_source)(name)
+                    .map_or(LiteralValue::Nil, |value| LiteralValue::Str(value.into())))
+            }
+            Native::Exists => {
+                let name = expect_str(native, &arguments[0], line)?;
+                Ok(LiteralValue::Bool(self.environment.contains(name)))
+            }
+            Native::WriteFile => {
+                if !self.allow_fs_write {
+                    return Err(RuntimeError::new(
+                        "Filesystem writes are not permitted.".to_string(),
+                        line,
+                    ));
+                }
+                let path = expect_str(native, &arguments[0], line)?;
+                let contents = expect_str(native, &arguments[1], line)?;
+                std::fs::write(path, contents).map_err(|err| {
+                    RuntimeError::new(format!("Could not write `{}`: {}.", path, err), line)
+                })?;
+                Ok(LiteralValue::Nil)
+            }
+            Native::ReadFile => {
+                if !self.allow_fs_read {
+                    return Err(RuntimeError::new(
+                        "Filesystem reads are not permitted.".to_string(),
+                        line,
+                    ));
+                }
+                let path = expect_str(native, &arguments[0], line)?;
+                let contents = std::fs::read_to_string(path).map_err(|err| {
+                    RuntimeError::new(format!("Could not read `{}`: {}.", path, err), line)
+                })?;
+                Ok(LiteralValue::Str(contents.into()))
+            }
+            Native::Round => {
+                let n = expect_num(native, &arguments[0], line)?;
+                let digits = match arguments.get(1) {
+                    Some(digits) => expect_num(native, digits, line)?,
+                    None => 0.0,
+                };
+                if digits < 0.0 || digits.fract() != 0.0 {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "`round` expects a non-negative integer of digits, not `{}`.",
+                            digits
+                        ),
+                        line,
+                    ));
+                }
+                let factor = 10f64.powf(digits);
+                let scaled = n * factor;
+                // past the precision of f64 there is nothing left to round
+                if !scaled.is_finite() {
+                    return Ok(LiteralValue::Num(n));
+                }
+                Ok(LiteralValue::Num(scaled.round() / factor))
+            }
+            Native::Between => match (&arguments[0], &arguments[1], &arguments[2]) {
+                (LiteralValue::Num(x), LiteralValue::Num(lo), LiteralValue::Num(hi)) => {
+                    Ok(LiteralValue::Bool(lo <= x && x <= hi))
+                }
+                // strings compare lexicographically by character
+                (LiteralValue::Str(x), LiteralValue::Str(lo), LiteralValue::Str(hi)) => {
+                    Ok(LiteralValue::Bool(lo <= x && x <= hi))
+                }
+                (x, lo, hi) => Err(RuntimeError::new(
+                    format!(
+                        "`between` expects three numbers or three strings, not a {}, a {} and a {}.",
+                        x.type_name(),
+                        lo.type_name(),
+                        hi.type_name()
+                    ),
+                    line,
+                )),
+            },
+            Native::GroupDigits => {
+                let n = expect_num(native, &arguments[0], line)?;
+                Ok(LiteralValue::Str(
+                    group_digits(&LiteralValue::Num(n).to_string()).into(),
+                ))
+            }
+            Native::Clock => Ok(LiteralValue::Num(self.clock.now())),
+            Native::Sleep => {
+                let seconds = expect_num(native, &arguments[0], line)?;
+                if !(seconds >= 0.0 && seconds.is_finite()) {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "`sleep` expects a non-negative number of seconds, not `{}`.",
+                            seconds
+                        ),
+                        line,
+                    ));
+                }
+                self.clock.sleep(seconds);
+                Ok(LiteralValue::Nil)
+            }
+            Native::Time => {
+                let start = self.clock.now();
+                self.call(arguments[0].clone(), vec![], line)?;
+                // the system clock may step backwards, an elapsed time never does
+                Ok(LiteralValue::Num((self.clock.now() - start).max(0.0)))
+            }
+            Native::Replace => {
+                let str = expect_str(native, &arguments[0], line)?;
+                let from = expect_str(native, &arguments[1], line)?;
+                let to = expect_str(native, &arguments[2], line)?;
+                // an empty pattern would match between every character
+                if from.is_empty() {
+                    return Ok(arguments[0].clone());
+                }
+                Ok(LiteralValue::Str(str.replace(from, to).into()))
+            }
+            Native::Version => Ok(LiteralValue::Str(env!("CARGO_PKG_VERSION").into())),
+            Native::Panic => {
+                let msg = expect_str(native, &arguments[0], line)?;
+                Err(RuntimeError::fatal(format!("Panic: {}", msg), line))
+            }
+            Native::AssertThrows => {
+                let function = &arguments[0];
+                // otherwise a call that can't even start would count as throwing
+                let callable = match function {
+                    LiteralValue::Function(function) => function.arity() == 0,
+                    LiteralValue::NativeFn(native) => native.arity().contains(&0),
+                    _ => false,
+                };
+                if !callable {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "`assert_throws` expects a function without parameters, not `{}`.",
+                            function
+                        ),
+                        line,
+                    ));
+                }
+                match self.call(function.clone(), vec![], line) {
+                    Err(err) if !err.is_fatal() => Ok(LiteralValue::Nil),
+                    Err(err) => Err(err),
+                    Ok(value) => Err(RuntimeError::new(
+                        format!(
+                            "Assertion failed: `{}` returned `{}` instead of throwing.",
+                            function, value
+                        ),
+                        line,
+                    )),
+                }
+            }
+            Native::PadLeft | Native::PadRight => {
+                let str = expect_str(native, &arguments[0], line)?;
+                let width = expect_num(native, &arguments[1], line)?;
+                if width < 0.0 || width.fract() != 0.0 {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "`{}` expects a non-negative integer width, not `{}`.",
+                            native.name(),
+                            width
+                        ),
+                        line,
+                    ));
+                }
+                let fill = match arguments.get(2) {
+                    Some(fill) => {
+                        let fill = expect_str(native, fill, line)?;
+                        let mut chars = fill.chars();
+                        match (chars.next(), chars.next()) {
+                            (Some(ch), None) => ch,
+                            _ => {
+                                return Err(RuntimeError::new(
+                                    format!(
+                                        "`{}` expects a single character fill, not `{}`.",
+                                        native.name(),
+                                        fill
+                                    ),
+                                    line,
+                                ))
+                            }
+                        }
+                    }
+                    None => ' ',
+                };
+                // the same memory bound as `string * count`
+                if width as usize > self.max_string_repeat {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "`{}` width must be at most {}.",
+                            native.name(),
+                            self.max_string_repeat
+                        ),
+                        line,
+                    ));
+                }
+                let missing = (width as usize).saturating_sub(str.chars().count());
+                let padding = fill.to_string().repeat(missing);
+                let padded = match native {
+                    Native::PadLeft => padding + str,
+                    _ => format!("{}{}", str, padding),
+                };
+                Ok(LiteralValue::Str(padded.into()))
+            }
+            Native::Sum => {
+                let numbers = expect_nums(native, &arguments[0], line)?;
+                Ok(LiteralValue::Num(numbers.iter().sum()))
+            }
+            Native::Avg | Native::Min | Native::Max => {
+                let numbers = expect_nums(native, &arguments[0], line)?;
+                if numbers.is_empty() {
+                    return Err(RuntimeError::new(
+                        format!("`{}` expects a non-empty list.", native.name()),
+                        line,
+                    ));
+                }
+                let result = match native {
+                    Native::Avg => numbers.iter().sum::<f64>() / numbers.len() as f64,
+                    Native::Min => numbers.into_iter().reduce(f64::min).unwrap(),
+                    _ => numbers.into_iter().reduce(f64::max).unwrap(),
+                };
+                Ok(LiteralValue::Num(result))
+            }
+            Native::Keys | Native::Values => {
+                let LiteralValue::Map(map) = &arguments[0] else {
+                    return Err(RuntimeError::new(
+                        format!(
+                            "`{}` expects a map, not a {}.",
+                            native.name(),
+                            arguments[0].type_name()
+                        ),
+                        line,
+                    ));
+                };
+                let elements = match native {
+                    Native::Keys => map
+                        .iter()
+                        .map(|(key, _)| LiteralValue::Str(key.clone()))
+                        .collect(),
+                    _ => map.iter().map(|(_, value)| value.clone()).collect(),
+                };
+                Ok(LiteralValue::List(Rc::new(elements)))
+            }
+            Native::Map => {
+                let elements = expect_list(native, &arguments[0], line)?;
+                let mapped = elements
+                    .iter()
+                    .map(|element| self.call(arguments[1].clone(), vec![element.clone()], line))
+                    .collect::<Result<_, _>>()?;
+                Ok(LiteralValue::List(Rc::new(mapped)))
+            }
+            Native::Filter => {
+                let elements = expect_list(native, &arguments[0], line)?;
+                let mut kept = vec![];
+                for element in elements {
+                    let keep = self.call(arguments[1].clone(), vec![element.clone()], line)?;
+                    if self.is_truthy(&keep) {
+                        kept.push(element.clone());
+                    }
+                }
+                Ok(LiteralValue::List(Rc::new(kept)))
+            }
+            Native::Reduce => {
+                let elements = expect_list(native, &arguments[0], line)?;
+                elements.iter().try_fold(arguments[2].clone(), |acc, element| {
+                    self.call(arguments[1].clone(), vec![acc, element.clone()], line)
+                })
+            }
+        }
+    }
+
+    /// run the body in a fresh scope over the scope captured at the declaration,
+    /// a call that finishes without `return` evaluates to `nil`
+    fn call_function(
+        &mut self,
+        function: &LoxFunction,
+        arguments: Vec<LiteralValue>,
+        line: usize,
+    ) -> Result<LiteralValue, RuntimeError> {
+        if self.call_depth >= self.max_call_depth {
+            return Err(RuntimeError::new(
+                format!(
+                    "Call stack depth exceeded in `{}`.",
+                    function.declaration.display_name()
+                ),
+                line,
+            ));
+        }
+        let name = function.declaration.display_name();
+        if self.trace {
+            let arguments = arguments
+                .iter()
+                .map(|argument| argument.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.trace_line(format_args!("call {}({})", name, arguments));
+        }
+        let enclosing = self.environment.replace_scope(Rc::clone(&function.closure));
+        self.call_depth += 1;
+        let result = self.execute_body(function, arguments);
+        self.call_depth -= 1;
+        self.environment.replace_scope(enclosing);
+        let value = self.returning.take().unwrap_or(LiteralValue::Nil);
+        if self.trace {
+            match &result {
+                Ok(()) => self.trace_line(format_args!("return {} -> {}", name, value)),
+                Err(_) => self.trace_line(format_args!("unwind {}", name)),
+            }
+        }
+        result.map(|_| value)
+    }
+
+    /// a function value closing over the current scope
+    fn make_function(&self, declaration: &Rc<FunctionStmt>) -> LiteralValue {
+        LiteralValue::Function(Rc::new(LoxFunction {
+            declaration: Rc::clone(declaration),
+            closure: self.environment.current_scope(),
+        }))
+    }
+
+    fn execute_body(
+        &mut self,
+        function: &LoxFunction,
+        arguments: Vec<LiteralValue>,
+    ) -> Result<(), RuntimeError> {
+        let declaration = &function.declaration;
+        self.environment.create_scope(declaration.body.line)?;
+        let result = declaration
+            .params
+            .iter()
+            .zip(arguments)
+            .try_for_each(|(param, argument)| {
+                self.environment.define(param, argument, declaration.line)
+            })
+            .and_then(|_| self.execute_stmts(&declaration.body.stmts));
+        // drop the scope even when a statement failed
+        self.environment.drop_scope();
+        result
+    }
+
+    /// short-circuits, the result is the deciding operand itself rather than a bool
+    fn evaluate_logical(&mut self, expr: &LogicalExpr) -> Result<LiteralValue, RuntimeError> {
+        let line = expr.operator.line;
+        let left = self.evaluate(&expr.left)?.ok_or_else(|| {
+            RuntimeError::new(format!("Expression `{}` has no value.", expr.left), line)
+        })?;
+        let left_truthy = self.is_truthy(&left);
+        let decided = match expr.operator.r#type {
+            TokenType::Or => left_truthy,
+            _ => !left_truthy,
+        };
+        if decided {
+            return Ok(left);
         }
+        self.evaluate(&expr.right)?.ok_or_else(|| {
+            RuntimeError::new(format!("Expression `{}` has no value.", expr.right), line)
+        })
+    }
+
+    fn evaluate_ternary(&mut self, expr: &TernaryExpr) -> Result<LiteralValue, RuntimeError> {
+        let condition = self.evaluate(&expr.condition)?.ok_or_else(|| {
+            RuntimeError::new(
+                format!("Expression `{}` has no value.", expr.condition),
+                expr.condition.line(),
+            )
+        })?;
+        let branch = if self.is_truthy(&condition) {
+            &expr.then_branch
+        } else {
+            &expr.else_branch
+        };
+        self.evaluate(branch)?.ok_or_else(|| {
+            RuntimeError::new(
+                format!("Expression `{}` has no value.", branch),
+                branch.line(),
+            )
+        })
+    }
+
+    fn evaluate_postfix(&mut self, expr: &PostfixExpr) -> Result<LiteralValue, RuntimeError> {
+        let old = self.look_up(&expr.var, expr.depth.get())?;
+        let LiteralValue::Num(num) = old else {
+            return Err(RuntimeError::new(
+                format!(
+                    "Operand of `{}` must be a number, not a {}.",
+                    expr.operator.lexeme,
+                    old.type_name()
+                ),
+                expr.operator.line,
+            ));
+        };
+        let new = match expr.operator.r#type {
+            TokenType::PlusPlus => num + 1.0,
+            _ => num - 1.0,
+        };
+        self.assign_variable(expr.var.clone(), expr.depth.get(), LiteralValue::Num(new))?;
+        Ok(old)
     }
 
     fn evaluate_binary(&mut self, expr: &BinaryExpr) -> Result<LiteralValue, RuntimeError> {
+        let line = expr.operator.line;
         let left = self.evaluate(&expr.left)?;
         let right = self.evaluate(&expr.right)?;
         let op_type = expr.operator.r#type;
         match (left, right, op_type) {
-            // divided by zero
-            // WARN. floating-point types cannot be used in patterns
-            // this was previously accepted by the compiler but is being phased out; it will become a hard error in a future release!
-            (Some(_), Some(LiteralValue::Num(0.0)), TokenType::Slash) => Err(RuntimeError::new(
-                "Divided by zero is not allowed.".to_string(),
-            )),
+            // any two values can be compared, different types are never equal
+            (Some(left), Some(right), TokenType::EqualEqual) => {
+                Ok(LiteralValue::Bool(left == right))
+            }
+            (Some(left), Some(right), TokenType::BangEqual) => {
+                Ok(LiteralValue::Bool(left != right))
+            }
             // evaluate numbers
             (
                 Some(LiteralValue::Num(left_num)),
@@ -158,66 +1043,357 @@ impl<W: std::io::Write> Interpreter<W> {
                 | TokenType::Minus
                 | TokenType::Slash
                 | TokenType::Star
-                | TokenType::EqualEqual
-                | TokenType::BangEqual
+                | TokenType::Percent
                 | TokenType::Greater
                 | TokenType::GreaterEqual
                 | TokenType::Less
                 | TokenType::LessEqual,
-            ) => Ok(match op_type {
-                TokenType::Plus => LiteralValue::Num(left_num + right_num),
-                TokenType::Minus => LiteralValue::Num(left_num - right_num),
-                TokenType::Slash => LiteralValue::Num(left_num / right_num),
-                TokenType::Star => LiteralValue::Num(left_num * right_num),
-                TokenType::EqualEqual => LiteralValue::Bool(left_num == right_num),
-                TokenType::BangEqual => LiteralValue::Bool(left_num != right_num),
-                TokenType::Greater => LiteralValue::Bool(left_num > right_num),
-                TokenType::GreaterEqual => LiteralValue::Bool(left_num >= right_num),
-                TokenType::Less => LiteralValue::Bool(left_num < right_num),
-                TokenType::LessEqual => LiteralValue::Bool(left_num <= right_num),
-                _ => unreachable!(),
-            }),
+            ) => {
+                // divided by zero is always an error rather than IEEE `inf`/`NaN`,
+                // `-0` included since it compares equal to `0`
+                if op_type == TokenType::Slash && right_num == 0.0 {
+                    return Err(RuntimeError::new(
+                        "Divided by zero is not allowed.".to_string(),
+                        line,
+                    ));
+                }
+                if op_type == TokenType::Percent && right_num == 0.0 {
+                    return Err(RuntimeError::new(
+                        "Modulo by zero is not allowed.".to_string(),
+                        line,
+                    ));
+                }
+                Ok(match op_type {
+                    TokenType::Plus => LiteralValue::Num(left_num + right_num),
+                    TokenType::Minus => LiteralValue::Num(left_num - right_num),
+                    TokenType::Slash => LiteralValue::Num(left_num / right_num),
+                    TokenType::Star => LiteralValue::Num(left_num * right_num),
+                    // the sign follows the dividend, like Rust and C
+                    TokenType::Percent => LiteralValue::Num(left_num % right_num),
+                    TokenType::Greater => LiteralValue::Bool(left_num > right_num),
+                    TokenType::GreaterEqual => LiteralValue::Bool(left_num >= right_num),
+                    TokenType::Less => LiteralValue::Bool(left_num < right_num),
+                    TokenType::LessEqual => LiteralValue::Bool(left_num <= right_num),
+                    _ => unreachable!(),
+                })
+            }
             // string concat
             (
                 Some(LiteralValue::Str(left_str)),
                 Some(LiteralValue::Str(right_str)),
                 TokenType::Plus,
-            ) => Ok(LiteralValue::Str(format!("{}{}", left_str, right_str))),
+            ) => Ok(LiteralValue::Str(
+                format!("{}{}", left_str, right_str).into(),
+            )),
+            // lexicographic string ordering
+            (
+                Some(LiteralValue::Str(left_str)),
+                Some(LiteralValue::Str(right_str)),
+                TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual,
+            ) => Ok(LiteralValue::Bool(match op_type {
+                TokenType::Greater => left_str > right_str,
+                TokenType::GreaterEqual => left_str >= right_str,
+                TokenType::Less => left_str < right_str,
+                _ => left_str <= right_str,
+            })),
+            // string repeat
+            (Some(LiteralValue::Str(str)), Some(LiteralValue::Num(count)), TokenType::Star) => self
+                .repeat_string(&str, count, line)
+                .map(|repeated| LiteralValue::Str(repeated.into())),
             // left_expr has no value
-            (None, Some(_), _) => Err(RuntimeError::new(format!(
-                "Expression `{}` has no value.",
-                expr.left.to_string(),
-            ))),
+            (None, Some(_), _) => Err(RuntimeError::new(
+                format!("Expression `{}` has no value.", expr.left),
+                line,
+            )),
             // right_expr has no value
-            (Some(_), None, _) => Err(RuntimeError::new(format!(
-                "Expression `{}` has no value.",
-                expr.right.to_string(),
-            ))),
+            (Some(_), None, _) => Err(RuntimeError::new(
+                format!("Expression `{}` has no value.", expr.right),
+                line,
+            )),
             // both no value
-            (None, None, _) => Err(RuntimeError::new(format!(
-                "Expression `{}` and `{}` has no value.",
-                expr.left.to_string(),
-                expr.right.to_string(),
-            ))),
-            (_, _, _) => Err(RuntimeError::new(format!(
-                "Expression `{}` can not be interpreted.",
-                expr.to_string()
-            ))),
+            (None, None, _) => Err(RuntimeError::new(
+                format!(
+                    "Expression `{}` and `{}` has no value.",
+                    expr.left, expr.right,
+                ),
+                line,
+            )),
+            // usually an uninitialized variable, so say so rather than name the types
+            (Some(LiteralValue::Nil), Some(_), _) | (Some(_), Some(LiteralValue::Nil), _) => {
+                Err(RuntimeError::new(
+                    format!("Operand to '{}' is nil.", expr.operator.lexeme),
+                    line,
+                ))
+            }
+            (Some(left), Some(right), _) => Err(RuntimeError::new(
+                format!(
+                    "Operator '{}' cannot be applied to {} and {}.",
+                    expr.operator.lexeme,
+                    left.type_name(),
+                    right.type_name(),
+                ),
+                line,
+            )),
+        }
+    }
+}
+
+pub struct InterpreterBuilder<W> {
+    interpreter: Interpreter<W>,
+}
+
+impl<W: std::io::Write> InterpreterBuilder<W> {
+    /// the largest count allowed in `string * count`
+    pub fn max_string_repeat(mut self, max: usize) -> Self {
+        self.interpreter.max_string_repeat = max;
+        self
+    }
+
+    /// keep running the next top-level statement after a runtime error
+    pub fn continue_on_error(mut self, enabled: bool) -> Self {
+        self.interpreter.continue_on_error = enabled;
+        self
+    }
+
+    /// print booleans as `1`/`0`
+    pub fn bool_as_int_output(mut self, enabled: bool) -> Self {
+        self.interpreter.bool_as_int_output = enabled;
+        self
+    }
+
+    /// the most nested function calls
+    pub fn max_recursion(mut self, max: usize) -> Self {
+        self.interpreter.max_call_depth = max;
+        self
+    }
+
+    /// the most nested scopes, the global scope included
+    pub fn max_scope_depth(mut self, max: usize) -> Self {
+        self.interpreter.environment.max_depth = max;
+        self
+    }
+
+    /// where `env(name)` looks variables up
+    pub fn env_source(mut self, source: EnvSource) -> Self {
+        self.interpreter.env_source = source;
+        self
+    }
+
+    /// let `write_file` touch the filesystem
+    pub fn allow_fs_write(mut self, enabled: bool) -> Self {
+        self.interpreter.allow_fs_write = enabled;
+        self
+    }
+
+    /// let `read_file` read the filesystem
+    pub fn allow_fs_read(mut self, enabled: bool) -> Self {
+        self.interpreter.allow_fs_read = enabled;
+        self
+    }
+
+    /// write prints out in large chunks instead of flushing every line
+    pub fn buffered_output(mut self, enabled: bool) -> Self {
+        self.interpreter.buffered_output = enabled;
+        self
+    }
+
+    /// receive print and error events
+    pub fn event_sink(mut self, sink: Box<dyn EventSink>) -> Self {
+        self.interpreter.event_sink = Some(sink);
+        self
+    }
+
+    /// what `clock`, `sleep` and `time` read
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.interpreter.clock = clock;
+        self
+    }
+
+    /// write a line to the trace output for every statement and call
+    pub fn trace(mut self, enabled: bool) -> Self {
+        self.interpreter.trace = enabled;
+        self
+    }
+
+    /// where `trace` lines go
+    pub fn trace_output(mut self, output: Box<dyn std::io::Write>) -> Self {
+        self.interpreter.trace_output = output;
+        self
+    }
+
+    pub fn build(self) -> Interpreter<W> {
+        self.interpreter
+    }
+}
+
+/// the string argument of a native, or an error naming the native
+fn expect_str(native: Native, value: &LiteralValue, line: usize) -> Result<&str, RuntimeError> {
+    match value {
+        LiteralValue::Str(str) => Ok(str),
+        _ => Err(RuntimeError::new(
+            format!(
+                "`{}` expects a string, not a {}.",
+                native.name(),
+                value.type_name()
+            ),
+            line,
+        )),
+    }
+}
+
+/// the number argument of a native, or an error naming the native
+fn expect_num(native: Native, value: &LiteralValue, line: usize) -> Result<f64, RuntimeError> {
+    match value {
+        LiteralValue::Num(n) => Ok(*n),
+        _ => Err(RuntimeError::new(
+            format!(
+                "`{}` expects a number, not a {}.",
+                native.name(),
+                value.type_name()
+            ),
+            line,
+        )),
+    }
+}
+
+/// the list argument of a native, or an error naming the native
+fn expect_list(
+    native: Native,
+    value: &LiteralValue,
+    line: usize,
+) -> Result<&[LiteralValue], RuntimeError> {
+    match value {
+        LiteralValue::List(elements) => Ok(elements),
+        _ => Err(RuntimeError::new(
+            format!(
+                "`{}` expects a list, not a {}.",
+                native.name(),
+                value.type_name()
+            ),
+            line,
+        )),
+    }
+}
+
+/// the list of numbers argument of a native, or an error naming the native
+fn expect_nums(
+    native: Native,
+    value: &LiteralValue,
+    line: usize,
+) -> Result<Vec<f64>, RuntimeError> {
+    expect_list(native, value, line)?
+        .iter()
+        .map(|element| match element {
+            LiteralValue::Num(n) => Ok(*n),
+            _ => Err(RuntimeError::new(
+                format!(
+                    "`{}` expects a list of numbers, not one containing a {}.",
+                    native.name(),
+                    element.type_name()
+                ),
+                line,
+            )),
+        })
+        .collect()
+}
+
+/// put a `,` between every three digits of the whole part of a printed number,
+/// the sign and the fraction are kept as they are
+fn group_digits(number: &str) -> String {
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", number),
+    };
+    let (whole, fraction) = match unsigned.find('.') {
+        Some(dot) => unsigned.split_at(dot),
+        None => (unsigned, ""),
+    };
+    // `inf` and `NaN` have no digits to group
+    if !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return number.to_string();
+    }
+    let mut grouped = String::with_capacity(number.len() + whole.len() / 3);
+    grouped.push_str(sign);
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
         }
+        grouped.push(digit);
     }
+    grouped.push_str(fraction);
+    grouped
 }
 
 /// util methods
 impl<W> Interpreter<W> {
-    fn is_truthy(&self, expr: &LiteralValue) -> bool {
-        match expr {
-            LiteralValue::Num(num) if *num == 0.0 => false,
-            LiteralValue::Str(str) if str == "" => false,
-            LiteralValue::Bool(b) => *b,
-            _ => true,
+    /// `depth` is the resolved scope hops, or `None` to search the scope chain
+    /// for programs that never went through the resolver
+    fn look_up(&self, name: &Token, depth: Option<usize>) -> Result<LiteralValue, RuntimeError> {
+        match depth {
+            Some(hops) => self.environment.get_at(hops, name),
+            None => self.environment.get(name),
+        }
+    }
+
+    fn assign_variable(
+        &mut self,
+        name: Token,
+        depth: Option<usize>,
+        value: LiteralValue,
+    ) -> Result<(), RuntimeError> {
+        match depth {
+            Some(hops) => self.environment.assign_at(hops, name, value),
+            None => self.environment.assign(name, value),
         }
     }
+
+    /// a trace line indented by the number of calls in progress
+    fn trace_line(&mut self, line: std::fmt::Arguments) {
+        writeln!(
+            self.trace_output,
+            "{:indent$}{}",
+            "",
+            line,
+            indent = self.call_depth * 2
+        )
+        .unwrap();
+    }
+
+    /// `"" * n` is always `""`, otherwise `count` must be an integer in `0..=max_string_repeat`
+    fn repeat_string(&self, str: &str, count: f64, line: usize) -> Result<String, RuntimeError> {
+        if str.is_empty() {
+            return Ok(String::new());
+        }
+        if count < 0.0 || count.fract() != 0.0 {
+            return Err(RuntimeError::new(
+                format!(
+                    "String repeat count must be a non-negative integer, not `{}`.",
+                    count
+                ),
+                line,
+            ));
+        }
+        if count > self.max_string_repeat as f64 {
+            return Err(RuntimeError::new(
+                format!(
+                    "String repeat count `{}` exceeds the limit of {}.",
+                    count, self.max_string_repeat
+                ),
+                line,
+            ));
+        }
+        Ok(str.repeat(count as usize))
+    }
+
+    /// only `nil` and `false` are falsy, everything else including `0` and `""` is truthy
+    fn is_truthy(&self, expr: &LiteralValue) -> bool {
+        !matches!(expr, LiteralValue::Nil | LiteralValue::Bool(false))
+    }
 }
+
 #[test]
 fn test_evaluate_unary() {
     let data = vec![
@@ -227,13 +1403,13 @@ fn test_evaluate_unary() {
         ("!!false", LiteralValue::Bool(false)),
         ("-1", LiteralValue::Num(-1.0)),
         ("-8.1", LiteralValue::Num(-8.1)),
-        ("\"a string\"", LiteralValue::Str(String::from("a string"))),
-        ("\"-1\"", LiteralValue::Str(String::from("-1"))),
+        ("\"a string\"", LiteralValue::Str("a string".into())),
+        ("\"-1\"", LiteralValue::Str("-1".into())),
     ];
 
     for (input, should_be) in data {
         let mut scanner = Scanner::new(String::from(input));
-        let tokens = scanner.scan_tokens();
+        let tokens = scanner.scan_tokens().unwrap();
         let mut parser = Parser::new(tokens);
         let expr = parser.parse_expression().unwrap();
         let mut interpreter = Interpreter::new(std::io::stdout());
@@ -255,18 +1431,1564 @@ fn test_evaluate_binary() {
         ("2 < 2.0", LiteralValue::Bool(false)),
         ("2 <= 2.0", LiteralValue::Bool(true)),
         ("0-8.1", LiteralValue::Num(-8.1)),
-        (
-            "\"one\" + \"two\"",
-            LiteralValue::Str(String::from("onetwo")),
-        ),
+        ("\"one\" + \"two\"", LiteralValue::Str("onetwo".into())),
     ];
 
     for (input, should_be) in data {
         let mut scanner = Scanner::new(String::from(input));
-        let tokens = scanner.scan_tokens();
+        let tokens = scanner.scan_tokens().unwrap();
         let mut parser = Parser::new(tokens);
         let expr = parser.parse_expression().unwrap();
         let mut interpreter = Interpreter::new(std::io::stdout());
         assert_eq!(should_be, interpreter.evaluate(&expr).unwrap().unwrap());
     }
 }
+
+/// records every write and flush it receives
+#[cfg(test)]
+struct FlushRecorder {
+    events: Vec<&'static str>,
+}
+
+#[cfg(test)]
+impl std::io::Write for FlushRecorder {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.events.push("write");
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.events.push("flush");
+        Ok(())
+    }
+}
+
+#[test]
+fn test_print_flushes_output() {
+    let tokens = Scanner::new(String::from("print 1;"))
+        .scan_tokens()
+        .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut interpreter = Interpreter::new(FlushRecorder { events: vec![] });
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(interpreter.output.events.last(), Some(&"flush"));
+}
+
+#[test]
+fn test_buffered_output_writes_in_chunks() {
+    // a print-heavy program reaches the writer in a few chunks
+    let tokens = Scanner::new(String::from("for (var i = 0; i < 5000; i++) print i;"))
+        .scan_tokens()
+        .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut interpreter = Interpreter::builder(FlushRecorder { events: vec![] })
+        .buffered_output(true)
+        .build();
+    interpreter.interpret(&stmts).unwrap();
+    let events = &interpreter.output.events;
+    assert!(events.len() < 20, "{} events", events.len());
+    assert_eq!(events.last(), Some(&"flush"));
+
+    // the text is the same as unbuffered, only written later
+    let tokens = Scanner::new(String::from("print 1; print \"a\";"))
+        .scan_tokens()
+        .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::builder(&mut buf).buffered_output(true).build();
+    interpreter.interpret(&stmts).unwrap();
+    drop(interpreter);
+    assert_eq!(String::from_utf8_lossy(&buf), "1\na\n");
+}
+
+#[test]
+fn test_string_repeat() {
+    let data = vec![
+        ("\"ab\" * 3", LiteralValue::Str("ababab".into())),
+        ("\"\" * 5", LiteralValue::Str("".into())),
+        ("\"x\" * 0", LiteralValue::Str("".into())),
+        ("\"\" * 100000000", LiteralValue::Str("".into())),
+    ];
+
+    for (input, should_be) in data {
+        let mut scanner = Scanner::new(String::from(input));
+        let tokens = scanner.scan_tokens().unwrap();
+        let mut parser = Parser::new(tokens);
+        let expr = parser.parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::stdout());
+        assert_eq!(should_be, interpreter.evaluate(&expr).unwrap().unwrap());
+    }
+
+    for (input, max) in [
+        ("\"x\" * 5", 4),
+        ("\"x\" * 100000000", DEFAULT_MAX_STRING_REPEAT),
+    ] {
+        let tokens = Scanner::new(String::from(input)).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::stdout());
+        interpreter.max_string_repeat = max;
+        assert!(interpreter.evaluate(&expr).is_err());
+    }
+}
+
+#[test]
+fn test_block_error_drops_scope() {
+    let tokens = Scanner::new(String::from("{ var a = 1; { print -\"a\"; } }"))
+        .scan_tokens()
+        .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut interpreter = Interpreter::new(std::io::stdout());
+    assert!(interpreter.interpret(&stmts).is_err());
+    assert_eq!(interpreter.environment.depth(), 1);
+}
+
+#[test]
+fn test_binary_type_error_message() {
+    let data = vec![
+        (
+            "\"a\" * true",
+            "[line 1] Operator '*' cannot be applied to string and bool.",
+        ),
+        ("\n\nnil - 1", "[line 3] Operand to '-' is nil."),
+        (
+            "1 +\n\"a\" / 2",
+            "[line 2] Operator '/' cannot be applied to string and number.",
+        ),
+    ];
+
+    for (input, should_be) in data {
+        let tokens = Scanner::new(String::from(input)).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::stdout());
+        let err = interpreter.evaluate(&expr).unwrap_err();
+        assert_eq!(err.to_string(), should_be);
+    }
+}
+
+#[test]
+fn test_continue_on_error() {
+    let src = "print -\"a\"; print 1; { print 2; print nil - 1; print 3; } print 4;";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    assert!(interpreter.interpret(&stmts).is_err());
+    assert!(interpreter.take_errors().is_empty());
+
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    interpreter.continue_on_error = true;
+    assert!(interpreter.interpret(&stmts).is_ok());
+    let errors = interpreter.take_errors();
+    assert_eq!(errors.len(), 2);
+    assert_ne!(errors[0].message(), errors[1].message());
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n2\n4\n");
+}
+
+#[test]
+fn test_scope_depth_limit() {
+    let src = format!("{}print 1;{}", "{".repeat(10), "}".repeat(10));
+    let tokens = Scanner::new(src).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+
+    let mut interpreter = Interpreter::new(std::io::sink());
+    interpreter.environment.max_depth = 8;
+    let err = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(err.message(), "Scope nesting limit exceeded.");
+    assert_eq!(interpreter.environment.depth(), 1);
+
+    interpreter.environment.max_depth = 11;
+    assert!(interpreter.interpret(&stmts).is_ok());
+}
+
+#[test]
+fn test_bool_as_int_output() {
+    let tokens = Scanner::new(String::from("print true; print false; print 1;"))
+        .scan_tokens()
+        .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "true\nfalse\n1\n");
+
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    interpreter.bool_as_int_output = true;
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n0\n1\n");
+}
+
+#[test]
+fn test_execute_if() {
+    let in_out = vec![
+        ("if (true) print 1;", "1\n"),
+        ("if (false) print 1; print 2;", "2\n"),
+        ("if (1 > 2) print 1; else print 2;", "2\n"),
+        (
+            "var a = 1; if (a == 1) { a = 2; print a; } else { print 3; }",
+            "2\n",
+        ),
+        // dangling else binds to the nearest if
+        ("if (true) if (false) print 1; else print 2;", "2\n"),
+        ("if (false) if (true) print 1; else print 2;", ""),
+        (
+            "var a = 2; if (a == 1) print 1; elif (a == 2) print 2; elif (a == 2) print 3; else print 4;",
+            "2\n",
+        ),
+        (
+            "if (false) print 1; elif (false) print 2; else { print 3; }",
+            "3\n",
+        ),
+        ("if (false) print 1; elif (false) print 2; print 3;", "3\n"),
+        ("unless (false) print 1;", "1\n"),
+        ("unless (true) print 1; print 2;", "2\n"),
+        ("unless (nil) { print 1; }", "1\n"),
+        ("var a = 1; unless (a == 1 or a == 2) print 1;", ""),
+    ];
+
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_unary_type_error_message() {
+    let tokens = Scanner::new(String::from("-\"x\"")).scan_tokens().unwrap();
+    let expr = Parser::new(tokens).parse_expression().unwrap();
+    let mut interpreter = Interpreter::new(std::io::stdout());
+    let err = interpreter.evaluate(&expr).unwrap_err();
+    assert_eq!(err.message(), "Operand must be a number, not a string.");
+}
+
+#[test]
+fn test_execute_loops() {
+    let in_out = vec![
+        ("for (var i = 0; i < 3; i = i + 1) print i;", "0\n1\n2\n"),
+        (
+            "var i = 3; while (i > 0) { print i; i = i - 1; }",
+            "3\n2\n1\n",
+        ),
+        ("var i = 0; for (; i < 2;) i++; print i;", "2\n"),
+        (
+            "var i = 0; until (i == 3) { print i; i = i + 1; }",
+            "0\n1\n2\n",
+        ),
+        ("var i = 5; until (i > 0) print i; print i;", "5\n"),
+    ];
+
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_logical_short_circuit() {
+    let in_out = vec![
+        ("print false or \"hi\";", "hi\n"),
+        ("print \"left\" or \"right\";", "left\n"),
+        ("print false and 1;", "false\n"),
+        ("print true and 2;", "2\n"),
+        (
+            "var hit = false; false and (hit = true); print hit;",
+            "false\n",
+        ),
+        (
+            "var hit = false; true or (hit = true); print hit;",
+            "false\n",
+        ),
+        (
+            "var hit = false; true and (hit = true); print hit;",
+            "true\n",
+        ),
+    ];
+
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_function_call() {
+    let in_out = vec![
+        ("func add(a, b) { return a + b; } print add(1, 2);", "3\n"),
+        ("func hi() { print \"hi\"; } print hi();", "hi\nnil\n"),
+        (
+            "func fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); } print fib(10);",
+            "55\n",
+        ),
+        (
+            "var a = \"global\"; func show() { print a; } { var a = \"local\"; show(); }",
+            "global\n",
+        ),
+        ("func f() {} print f;", "<fn f>\n"),
+    ];
+
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+
+    let errors = vec![
+        (
+            "func add(a, b) { return a + b; } add(1);",
+            "[line 1] Expected 2 arguments but got 1.",
+        ),
+        (
+            "var a = 1; a();",
+            "[line 1] Can only call functions, not a number.",
+        ),
+        (
+            "func f() { f(); } f();",
+            "[line 1] Call stack depth exceeded in `f`.",
+        ),
+    ];
+    for (src, expected) in errors {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.interpret(&stmts).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_env_native() {
+    let src = "print env(\"RLOX_GREETING\"); print env(\"RLOX_UNKNOWN\");";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    interpreter.env_source = Box::new(|name| (name == "RLOX_GREETING").then(|| "hi".to_string()));
+    interpreter.interpret(&stmts).unwrap();
+    drop(interpreter);
+    assert_eq!(String::from_utf8_lossy(&buf), "hi\nnil\n");
+
+    let tokens = Scanner::new(String::from("env(1);")).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let err = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(err.message(), "`env` expects a string, not a number.");
+}
+
+#[test]
+fn test_early_return() {
+    let src = "
+        func find(limit) {
+            var i = 0;
+            while (true) {
+                {
+                    if (i == limit) {
+                        return i * 10;
+                    }
+                }
+                i = i + 1;
+            }
+            print \"unreachable\";
+        }
+        func side() { if (true) return; print \"unreachable\"; }
+        print find(3);
+        print side();
+        for (var i = 0; i < 2; i = i + 1) print find(i);
+    ";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    interpreter.interpret(&stmts).unwrap();
+    drop(interpreter);
+    assert_eq!(String::from_utf8_lossy(&buf), "30\nnil\n0\n10\n");
+
+    let tokens = Scanner::new(String::from("print 1; return 2; print 3;"))
+        .scan_tokens()
+        .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    let err = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(err.message(), "Cannot return outside of a function.");
+    drop(interpreter);
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n");
+}
+
+#[test]
+fn test_exists_native() {
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    let src = "
+        var defined = 1;
+        print exists(\"defined\");
+        print exists(\"undefined\");
+        print exists(\"exists\");
+        {
+            var local = nil;
+            print exists(\"local\");
+        }
+        print exists(\"local\");
+    ";
+    let tokens = Scanner::new(src.to_string()).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        "true\nfalse\ntrue\ntrue\nfalse\n"
+    );
+
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let err = interpreter.eval_str("exists(1)").unwrap_err();
+    assert!(
+        matches!(err, LoxError::RuntimeError(err) if err.message() == "`exists` expects a string, not a number.")
+    );
+}
+
+#[test]
+fn test_write_file_native() {
+    let path = std::env::temp_dir().join(format!("rlox_write_{}.txt", std::process::id()));
+    let src = format!("write_file(\"{}\", \"written\");", path.display());
+    let tokens = Scanner::new(src).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let err = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(err.message(), "Filesystem writes are not permitted.");
+    assert!(!path.exists());
+
+    interpreter.allow_fs_write = true;
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "written");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_read_file_native() {
+    let path = std::env::temp_dir().join(format!("rlox_read_{}.txt", std::process::id()));
+    std::fs::write(&path, "read back").unwrap();
+    let src = format!("read_file(\"{}\")", path.display());
+    let tokens = Scanner::new(src).scan_tokens().unwrap();
+    let expr = Parser::new(tokens).parse_expression().unwrap();
+
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let err = interpreter.evaluate(&expr).unwrap_err();
+    assert_eq!(err.message(), "Filesystem reads are not permitted.");
+
+    interpreter.allow_fs_read = true;
+    assert_eq!(
+        interpreter.evaluate(&expr).unwrap(),
+        Some(LiteralValue::Str("read back".into()))
+    );
+    std::fs::remove_file(&path).unwrap();
+
+    let err = interpreter.evaluate(&expr).unwrap_err();
+    assert!(
+        err.message().contains("Could not read `"),
+        "{}",
+        err.message()
+    );
+}
+
+#[test]
+fn test_divide_by_zero() {
+    for input in ["1 / 0", "0 / 0", "1 / -0", "1 / (2 - 2)"] {
+        let tokens = Scanner::new(String::from(input)).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.evaluate(&expr).unwrap_err();
+        assert_eq!(err.message(), "Divided by zero is not allowed.");
+    }
+
+    let tokens = Scanner::new(String::from("1 / 4")).scan_tokens().unwrap();
+    let expr = Parser::new(tokens).parse_expression().unwrap();
+    let mut interpreter = Interpreter::new(std::io::sink());
+    assert_eq!(
+        interpreter.evaluate(&expr).unwrap(),
+        Some(LiteralValue::Num(0.25))
+    );
+}
+
+#[test]
+fn test_modulo() {
+    let data = vec![
+        ("7 % 3 == 1", LiteralValue::Bool(true)),
+        ("-7 % 3", LiteralValue::Num(-1.0)),
+        ("7.5 % 2", LiteralValue::Num(1.5)),
+        ("1 + 7 % 4 * 2", LiteralValue::Num(7.0)),
+    ];
+    for (input, should_be) in data {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(interpreter.eval_str(input).unwrap(), should_be, "{}", input);
+    }
+
+    for input in ["1 % 0", "1 % -0"] {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert!(matches!(
+            interpreter.eval_str(input),
+            Err(LoxError::RuntimeError(err)) if err.message() == "Modulo by zero is not allowed."
+        ));
+    }
+}
+
+#[test]
+fn test_ternary() {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    assert_eq!(
+        interpreter.eval_str("true ? \"yes\" : \"no\"").unwrap(),
+        LiteralValue::Str("yes".into())
+    );
+    assert_eq!(
+        interpreter.eval_str("nil ? 1 : 0 ? 2 : 3").unwrap(),
+        LiteralValue::Num(2.0)
+    );
+
+    // the branch not taken is never evaluated, so it neither fails nor has effects
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    let src = "var a = 0; print true ? a : panic(\"no\"); print false ? a++ : a; print a;";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "0\n0\n0\n");
+}
+
+#[test]
+fn test_higher_order_functions() {
+    let in_out = vec![
+        (
+            "func twice(f, x) { return f(f(x)); } func inc(x) { return x + 1; } print twice(inc, 1);",
+            "3\n",
+        ),
+        (
+            "func pick() { return env; } print pick()(\"RLOX_UNSET_FOR_TEST\");",
+            "nil\n",
+        ),
+        ("func id(x) { return x; } var f = id; print f(id)(2);", "2\n"),
+        (
+            "print map([1, 2, 3], func(x) { return x * 2; });",
+            "[2, 4, 6]\n",
+        ),
+        ("print map([], clock);", "[]\n"),
+        (
+            "func apply(f) { return map([\"a\"], f); } print apply(func(s) { return s + s; });",
+            "[\"aa\"]\n",
+        ),
+    ];
+
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.env_source = Box::new(|_| None);
+        interpreter.interpret(&stmts).unwrap();
+        drop(interpreter);
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+
+    let mut interpreter = Interpreter::new(std::io::sink());
+    for (input, expected) in [
+        ("map(1, clock)", "`map` expects a list, not a number."),
+        ("map([1], 1)", "Can only call functions, not a number."),
+        ("map([1], clock)", "Expected 0 arguments but got 1."),
+    ] {
+        let err = interpreter.eval_str(input).unwrap_err();
+        assert!(
+            matches!(err, LoxError::RuntimeError(ref err) if err.message() == expected),
+            "{}: {:?}",
+            input,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_anonymous_functions() {
+    let in_out = vec![
+        ("var f = func (x) { return x * 2; }; print f(21);", "42\n"),
+        (
+            "func map2(f, a, b) { return f(a) + f(b); } print map2(func (x) { return x * x; }, 3, 4);",
+            "25\n",
+        ),
+        (
+            "func adder(n) { return func (x) { return x + n; }; } var add2 = adder(2); print add2(5);",
+            "7\n",
+        ),
+        ("print func () { return 1; }();", "1\n"),
+        ("func () { print \"called\"; }();", "called\n"),
+        ("print func (a, b) {};", "<anonymous fn>\n"),
+    ];
+
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        drop(interpreter);
+        assert_eq!(String::from_utf8_lossy(&buf), expected, "{}", src);
+    }
+
+    let src = "var f = func () { return f(); }; f();";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut interpreter = Interpreter::builder(std::io::sink())
+        .max_recursion(10)
+        .build();
+    let err = interpreter.interpret(&stmts).unwrap_err();
+    assert_eq!(
+        err.message(),
+        "Call stack depth exceeded in `<anonymous fn>`."
+    );
+}
+
+#[test]
+fn test_equality_across_types() {
+    let data = vec![
+        ("\"a\" == \"a\"", true),
+        ("\"a\" == \"b\"", false),
+        ("true != false", true),
+        ("nil == nil", true),
+        ("nil == false", false),
+        ("1 == \"1\"", false),
+        ("1 != \"1\"", true),
+        ("0 == 0", true),
+    ];
+
+    for (input, should_be) in data {
+        let tokens = Scanner::new(String::from(input)).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(
+            interpreter.evaluate(&expr).unwrap(),
+            Some(LiteralValue::Bool(should_be)),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_string_comparison() {
+    let data = vec![
+        ("\"apple\" < \"banana\"", true),
+        ("\"banana\" < \"apple\"", false),
+        ("\"banana\" > \"apple\"", true),
+        ("\"app\" < \"apple\"", true),
+        ("\"a\" <= \"a\"", true),
+        ("\"a\" >= \"b\"", false),
+        ("\"Z\" < \"a\"", true),
+    ];
+
+    for (input, should_be) in data {
+        let tokens = Scanner::new(String::from(input)).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(
+            interpreter.evaluate(&expr).unwrap(),
+            Some(LiteralValue::Bool(should_be)),
+            "{}",
+            input
+        );
+    }
+
+    let tokens = Scanner::new(String::from("\"a\" < 1"))
+        .scan_tokens()
+        .unwrap();
+    let expr = Parser::new(tokens).parse_expression().unwrap();
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let err = interpreter.evaluate(&expr).unwrap_err();
+    assert_eq!(
+        err.message(),
+        "Operator '<' cannot be applied to string and number."
+    );
+}
+
+#[test]
+fn test_event_sink() {
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Recording {
+        events: Rc<RefCell<Vec<String>>>,
+    }
+    impl EventSink for Recording {
+        fn on_print(&mut self, text: &str, line: usize) {
+            self.events
+                .borrow_mut()
+                .push(format!("print {} at {}", text, line));
+        }
+        fn on_error(&mut self, err: &RuntimeError) {
+            self.events.borrow_mut().push(format!("error {}", err));
+        }
+    }
+
+    let recording = Recording::default();
+    let events = Rc::clone(&recording.events);
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::builder(&mut buf)
+        .event_sink(Box::new(recording))
+        .build();
+    let tokens = Scanner::new(String::from(
+        "print 1;\ncapture { print 2; } into a;\nprint -\"x\";",
+    ))
+    .scan_tokens()
+    .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    assert!(interpreter.interpret(&stmts).is_err());
+    drop(interpreter);
+
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n");
+    assert_eq!(
+        *events.borrow(),
+        vec![
+            "print 1 at 1".to_string(),
+            "error [line 3] Operand must be a number, not a string.".to_string()
+        ]
+    );
+}
+
+#[test]
+fn test_round_native() {
+    let data = vec![
+        ("round(3.14159, 2)", 314.0 / 100.0),
+        ("round(2.5, 0)", 3.0),
+        ("round(2.5)", 3.0),
+        ("round(-2.5)", -3.0),
+        ("round(1234.5678, 1)", 1234.6),
+        ("round(1.5, 400)", 1.5),
+    ];
+    for (input, should_be) in data {
+        let tokens = Scanner::new(String::from(input)).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(
+            interpreter.evaluate(&expr).unwrap(),
+            Some(LiteralValue::Num(should_be)),
+            "{}",
+            input
+        );
+    }
+
+    let errors = vec![
+        (
+            "round(1.0, -1)",
+            "`round` expects a non-negative integer of digits, not `-1`.",
+        ),
+        (
+            "round(1.0, 0.5)",
+            "`round` expects a non-negative integer of digits, not `0.5`.",
+        ),
+        ("round(\"1\")", "`round` expects a number, not a string."),
+        ("round()", "Expected 1 to 2 arguments but got 0."),
+        ("round(1, 2, 3)", "Expected 1 to 2 arguments but got 3."),
+    ];
+    for (input, expected) in errors {
+        let tokens = Scanner::new(String::from(input)).scan_tokens().unwrap();
+        let expr = Parser::new(tokens).parse_expression().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.evaluate(&expr).unwrap_err();
+        assert_eq!(err.message(), expected, "{}", input);
+    }
+}
+
+#[test]
+fn test_eval_str() {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let tokens = Scanner::new(String::from("var a = 2;"))
+        .scan_tokens()
+        .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    interpreter.interpret(&stmts).unwrap();
+
+    assert_eq!(
+        interpreter.eval_str("a * 3").unwrap(),
+        LiteralValue::Num(6.0)
+    );
+    assert_eq!(
+        interpreter.eval_str("a = a + 1").unwrap(),
+        LiteralValue::Num(3.0)
+    );
+    assert_eq!(interpreter.eval_str("a").unwrap(), LiteralValue::Num(3.0));
+
+    assert!(matches!(
+        interpreter.eval_str("b"),
+        Err(LoxError::RuntimeError(_))
+    ));
+    assert!(matches!(
+        interpreter.eval_str("a *"),
+        Err(LoxError::ParseErrors(_))
+    ));
+    assert!(matches!(
+        interpreter.eval_str("a 1"),
+        Err(LoxError::ParseErrors(_))
+    ));
+    assert!(matches!(
+        interpreter.eval_str("a @"),
+        Err(LoxError::ScanErrors(_))
+    ));
+}
+
+#[test]
+fn test_trace() {
+    use std::cell::RefCell;
+
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+    impl std::io::Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let trace = Rc::new(RefCell::new(vec![]));
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::builder(&mut buf)
+        .trace(true)
+        .trace_output(Box::new(Shared(Rc::clone(&trace))))
+        .build();
+    let src = "func add(a, b) { return a + b; }\nprint add(1, 2);";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    interpreter.interpret(&stmts).unwrap();
+    drop(interpreter);
+
+    assert_eq!(String::from_utf8_lossy(&buf), "3\n");
+    let trace = String::from_utf8(trace.take()).unwrap();
+    let lines: Vec<&str> = trace.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", trace);
+    assert!(lines[0].starts_with("exec (func add"));
+    assert!(lines[1].starts_with("exec (print"));
+    assert_eq!(lines[2], "call add(1, 2)");
+    assert!(lines[3].starts_with("  exec (return"));
+    assert_eq!(lines[4], "return add -> 3");
+}
+
+#[test]
+fn test_between_native() {
+    let data = vec![
+        ("between(2, 1, 3)", true),
+        ("between(1, 1, 3)", true),
+        ("between(3, 1, 3)", true),
+        ("between(0.5, 1, 3)", false),
+        ("between(4, 1, 3)", false),
+        // an empty range holds nothing
+        ("between(2, 3, 1)", false),
+        ("between(\"b\", \"a\", \"c\")", true),
+        ("between(\"apple\", \"apple\", \"banana\")", true),
+        ("between(\"b\", \"ba\", \"c\")", false),
+        ("between(\"B\", \"a\", \"z\")", false),
+    ];
+    for (input, should_be) in data {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(
+            interpreter.eval_str(input).unwrap(),
+            LiteralValue::Bool(should_be),
+            "{}",
+            input
+        );
+    }
+
+    let errors = vec![
+        (
+            "between(\"2\", 1, 3)",
+            "`between` expects three numbers or three strings, not a string, a number and a number.",
+        ),
+        (
+            "between(2, nil, 3)",
+            "`between` expects three numbers or three strings, not a number, a nil and a number.",
+        ),
+        (
+            "between(\"b\", \"a\", true)",
+            "`between` expects three numbers or three strings, not a string, a string and a bool.",
+        ),
+        ("between(2, 1)", "Expected 3 arguments but got 2."),
+    ];
+    for (input, expected) in errors {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.eval_str(input).unwrap_err();
+        assert!(
+            matches!(err, LoxError::RuntimeError(ref err) if err.message() == expected),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_group_digits_native() {
+    let data = vec![
+        ("group_digits(1234567)", "1,234,567"),
+        ("group_digits(123)", "123"),
+        ("group_digits(1000)", "1,000"),
+        ("group_digits(0)", "0"),
+        ("group_digits(-1234567)", "-1,234,567"),
+        ("group_digits(-100)", "-100"),
+        ("group_digits(1234.5678)", "1,234.5678"),
+        ("group_digits(-98765.25)", "-98,765.25"),
+        ("group_digits(0.125)", "0.125"),
+    ];
+    for (input, should_be) in data {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(
+            interpreter.eval_str(input).unwrap(),
+            LiteralValue::Str(should_be.into()),
+            "{}",
+            input
+        );
+    }
+
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let err = interpreter.eval_str("group_digits(\"1000\")").unwrap_err();
+    assert!(
+        matches!(err, LoxError::RuntimeError(err) if err.message() == "`group_digits` expects a number, not a string.")
+    );
+}
+
+#[test]
+fn test_clock_native() {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let before = interpreter.eval_str("clock()").unwrap();
+    let after = interpreter.eval_str("clock()").unwrap();
+    match (before, after) {
+        (LiteralValue::Num(before), LiteralValue::Num(after)) => {
+            assert!(before > 0.0 && after >= before)
+        }
+        _ => panic!("clock() should return numbers"),
+    }
+    assert_eq!(
+        interpreter.eval_str("clock").unwrap().to_string(),
+        "<native fn clock>"
+    );
+
+    let err = interpreter.eval_str("clock(1)").unwrap_err();
+    assert!(
+        matches!(err, LoxError::RuntimeError(err) if err.message() == "Expected 0 arguments but got 1.")
+    );
+}
+
+#[test]
+fn test_sleep_and_time_natives() {
+    use std::cell::Cell;
+
+    /// time only moves when the program sleeps
+    struct FakeClock(Rc<Cell<f64>>);
+
+    impl Clock for FakeClock {
+        fn now(&mut self) -> f64 {
+            self.0.get()
+        }
+
+        fn sleep(&mut self, seconds: f64) {
+            self.0.set(self.0.get() + seconds);
+        }
+    }
+
+    let now = Rc::new(Cell::new(100.0));
+    let mut interpreter = Interpreter::builder(std::io::sink())
+        .clock(Box::new(FakeClock(now.clone())))
+        .build();
+    assert!(matches!(
+        interpreter.eval_str("sleep(0)").unwrap(),
+        LiteralValue::Nil
+    ));
+    interpreter.eval_str("sleep(2.5)").unwrap();
+    assert_eq!(now.get(), 102.5);
+    assert_eq!(
+        interpreter.eval_str("clock()").unwrap().to_string(),
+        "102.5"
+    );
+    assert_eq!(
+        interpreter
+            .eval_str("time(func () { sleep(1.5); })")
+            .unwrap()
+            .to_string(),
+        "1.5"
+    );
+
+    let err = interpreter.eval_str("sleep(-1)").unwrap_err();
+    assert!(
+        matches!(err, LoxError::RuntimeError(err) if err.message() == "`sleep` expects a non-negative number of seconds, not `-1`.")
+    );
+    let err = interpreter.eval_str("time(1)").unwrap_err();
+    assert!(
+        matches!(err, LoxError::RuntimeError(err) if err.message() == "Can only call functions, not a number.")
+    );
+    let err = interpreter.eval_str("time(func (a) {})").unwrap_err();
+    assert!(
+        matches!(err, LoxError::RuntimeError(err) if err.message() == "Expected 1 arguments but got 0.")
+    );
+
+    // the real clock
+    let mut interpreter = Interpreter::new(std::io::sink());
+    match interpreter.eval_str("time(func () { sleep(0); })").unwrap() {
+        LiteralValue::Num(elapsed) => assert!(elapsed >= 0.0),
+        other => panic!("time() should return a number, not {}", other),
+    }
+}
+
+#[test]
+fn test_closures() {
+    let src = "
+        func makeCounter() {
+            var count = 0;
+            func increment() {
+                count = count + 1;
+                return count;
+            }
+            return increment;
+        }
+        var counter = makeCounter();
+        print counter();
+        print counter();
+        var other = makeCounter();
+        print other();
+        print counter();
+
+        var greeting = \"hi\";
+        func greet() { print greeting; }
+        greeting = \"hello\";
+        greet();
+    ";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    interpreter.interpret(&stmts).unwrap();
+    assert_eq!(interpreter.environment.depth(), 1);
+    drop(interpreter);
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n2\n1\n3\nhello\n");
+}
+
+#[test]
+fn test_pad_natives() {
+    let data = vec![
+        ("pad_left(\"7\", 3, \"0\")", "007"),
+        ("pad_right(\"ab\", 4, \".\")", "ab.."),
+        ("pad_left(\"ab\", 4)", "  ab"),
+        ("pad_right(\"é\", 3, \"—\")", "é——"),
+        ("pad_left(\"long\", 2, \"*\")", "long"),
+        ("pad_right(\"four\", 4)", "four"),
+    ];
+    for (input, should_be) in data {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(
+            interpreter.eval_str(input).unwrap(),
+            LiteralValue::Str(should_be.into()),
+            "{}",
+            input
+        );
+    }
+
+    let errors = vec![
+        (
+            "pad_left(\"a\", 3, \"ab\")",
+            "`pad_left` expects a single character fill, not `ab`.",
+        ),
+        (
+            "pad_right(\"a\", 3, \"\")",
+            "`pad_right` expects a single character fill, not ``.",
+        ),
+        (
+            "pad_left(\"a\", -1)",
+            "`pad_left` expects a non-negative integer width, not `-1`.",
+        ),
+        (
+            "pad_right(1, 3)",
+            "`pad_right` expects a string, not a number.",
+        ),
+        (
+            "pad_left(\"a\", 2000000)",
+            "`pad_left` width must be at most 1048576.",
+        ),
+    ];
+    for (input, expected) in errors {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.eval_str(input).unwrap_err();
+        assert!(
+            matches!(err, LoxError::RuntimeError(ref err) if err.message() == expected),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_replace_native() {
+    let data = vec![
+        ("replace(\"a-b-c\", \"-\", \"+\")", "a+b+c"),
+        ("replace(\"aaa\", \"aa\", \"b\")", "ba"),
+        ("replace(\"abc\", \"x\", \"y\")", "abc"),
+        ("replace(\"héllo\", \"é\", \"\")", "hllo"),
+        ("replace(\"\", \"a\", \"b\")", ""),
+        ("replace(\"abc\", \"\", \"x\")", "abc"),
+    ];
+    for (input, should_be) in data {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        assert_eq!(
+            interpreter.eval_str(input).unwrap(),
+            LiteralValue::Str(should_be.into()),
+            "{}",
+            input
+        );
+    }
+
+    let errors = vec![
+        (
+            "replace(1, \"a\", \"b\")",
+            "`replace` expects a string, not a number.",
+        ),
+        (
+            "replace(\"a\", nil, \"b\")",
+            "`replace` expects a string, not a nil.",
+        ),
+        (
+            "replace(\"a\", \"a\", true)",
+            "`replace` expects a string, not a bool.",
+        ),
+    ];
+    for (input, expected) in errors {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.eval_str(input).unwrap_err();
+        assert!(
+            matches!(err, LoxError::RuntimeError(ref err) if err.message() == expected),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_version_native() {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let version = interpreter.eval_str("version()").unwrap();
+    assert_eq!(version, LiteralValue::Str(env!("CARGO_PKG_VERSION").into()));
+    assert!(!version.to_string().is_empty());
+    assert!(interpreter.eval_str("version(1)").is_err());
+}
+
+#[test]
+fn test_panic_native() {
+    let src = "print 1;\nif (true) panic(\"boom\");\nprint 2;";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    let err = interpreter.interpret(&stmts).unwrap_err();
+    assert!(err.is_fatal());
+    assert_eq!(err.to_string(), "[line 2] Panic: boom");
+
+    // other errors are recovered from, a panic is not
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    interpreter.continue_on_error = true;
+    assert!(interpreter.interpret(&stmts).is_err());
+    assert!(interpreter.take_errors().is_empty());
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n");
+
+    let mut interpreter = Interpreter::new(std::io::sink());
+    // a bad argument is an ordinary error
+    assert!(matches!(
+        interpreter.eval_str("panic(1)"),
+        Err(LoxError::RuntimeError(err)) if !err.is_fatal()
+    ));
+
+    // neither `catch` nor `finally` runs for a panic
+    let src = "try { panic(\"boom\"); } catch (e) { print e; } finally { print 2; }";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    let err = interpreter.interpret(&stmts).unwrap_err();
+    assert!(err.is_fatal());
+    assert_eq!(String::from_utf8_lossy(&buf), "");
+}
+
+#[test]
+fn test_assert_throws_native() {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    assert!(matches!(
+        interpreter
+            .eval_str("assert_throws(func () { return 1 / 0; })")
+            .unwrap(),
+        LiteralValue::Nil
+    ));
+    // the interpreter is usable after the caught error
+    assert_eq!(
+        interpreter
+            .eval_str("(func () { return 1 + 1; })()")
+            .unwrap(),
+        LiteralValue::Num(2.0)
+    );
+
+    let errors = vec![
+        (
+            "assert_throws(func(){ return 1; })",
+            "Assertion failed: `<anonymous fn>` returned `1` instead of throwing.",
+        ),
+        (
+            "assert_throws(clock)",
+            "Assertion failed: `<native fn clock>` returned `",
+        ),
+        (
+            "assert_throws(func (a) { return -a; })",
+            "`assert_throws` expects a function without parameters, not `<anonymous fn>`.",
+        ),
+        (
+            "assert_throws(1)",
+            "`assert_throws` expects a function without parameters, not `1`.",
+        ),
+    ];
+    for (input, expected) in errors {
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.eval_str(input).unwrap_err();
+        assert!(
+            matches!(err, LoxError::RuntimeError(ref err) if err.message().starts_with(expected)),
+            "{}: {:?}",
+            input,
+            err
+        );
+    }
+
+    // a panic still aborts
+    let mut interpreter = Interpreter::new(std::io::sink());
+    assert!(matches!(
+        interpreter.eval_str("assert_throws(func () { panic(\"boom\"); })"),
+        Err(LoxError::RuntimeError(err)) if err.is_fatal()
+    ));
+}
+
+#[test]
+fn test_lists() {
+    let in_out = vec![
+        ("print [];", "[]\n"),
+        (
+            "print [1, \"two\", [3, nil], true];",
+            "[1, \"two\", [3, nil], true]\n",
+        ),
+        ("var xs = [1, 2, 3]; print xs[0] + xs[2];", "4\n"),
+        ("print [[1, 2], [3]][0][1];", "2\n"),
+        (
+            "var fs = [func (x) { return x * 2; }]; print fs[0](4);",
+            "8\n",
+        ),
+        ("print [1, [2]] == [1, [2]];", "true\n"),
+        ("print [1] == [2];", "false\n"),
+        ("print [1] == 1;", "false\n"),
+    ];
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected, "{}", src);
+    }
+
+    let errors = vec![
+        (
+            "[1, 2][2];",
+            "[line 1] Index 2 is out of range for a list of length 2.",
+        ),
+        (
+            "[1, 2][-1];",
+            "[line 1] Index -1 is out of range for a list of length 2.",
+        ),
+        (
+            "[1, 2][0.5];",
+            "[line 1] List index must be a whole number, not `0.5`.",
+        ),
+        (
+            "[1, 2][\"0\"];",
+            "[line 1] List index must be a number, not a string.",
+        ),
+        (
+            "1[0];",
+            "[line 1] Can only index lists and maps, not a number.",
+        ),
+    ];
+    for (src, expected) in errors {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.interpret(&stmts).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_maps() {
+    let in_out = vec![
+        ("print {};", "{}\n"),
+        (
+            "print {\"a\": 1, \"b\": [2, \"c\"], \"d\": {\"e\": nil}};",
+            "{\"a\": 1, \"b\": [2, \"c\"], \"d\": {\"e\": nil}}\n",
+        ),
+        ("var m = {\"a\": 1}; print m[\"a\"];", "1\n"),
+        ("print {\"k\": 1, \"k\": 2};", "{\"k\": 2}\n"),
+        ("var k = \"a\"; print {k + \"b\": 1};", "{\"ab\": 1}\n"),
+        (
+            "print {\"a\": 1, \"b\": 2} == {\"b\": 2, \"a\": 1};",
+            "true\n",
+        ),
+        ("print {\"a\": 1} == {\"a\": 2};", "false\n"),
+    ];
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected, "{}", src);
+    }
+
+    let errors = vec![
+        (
+            "print {1: 2};",
+            "[line 1] Map keys must be strings, not a number.",
+        ),
+        (
+            "print {\"a\": 1}[nil];",
+            "[line 1] Map keys must be strings, not a nil.",
+        ),
+        (
+            "print {\"a\": 1}[\"b\"];",
+            "[line 1] Key \"b\" is not in the map.",
+        ),
+        ("keys([]);", "[line 1] `keys` expects a map, not a list."),
+    ];
+    for (src, expected) in errors {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new(std::io::sink());
+        let err = interpreter.interpret(&stmts).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    }
+}
+
+#[test]
+fn test_map_insertion_order() {
+    let src = "var m = {\"zebra\": 1, \"apple\": 2, \"mango\": 3, \"kiwi\": 4};
+        print m; print keys(m); print values(m);";
+    let expected = "{\"zebra\": 1, \"apple\": 2, \"mango\": 3, \"kiwi\": 4}\n\
+        [\"zebra\", \"apple\", \"mango\", \"kiwi\"]\n[1, 2, 3, 4]\n";
+    // a hashed map would reorder the keys between runs
+    for _ in 0..10 {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_filter_reduce_natives() {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let data = vec![
+        (
+            "filter([1, 2, 3, 4], func(x) { return x % 2 == 0; })",
+            "[2, 4]",
+        ),
+        (
+            "filter([1, nil, false, 0], func(x) { return x; })",
+            "[1, 0]",
+        ),
+        ("filter([], func(x) { return true; })", "[]"),
+        (
+            "reduce([1, 2, 3], func(acc, x) { return acc + x; }, 0)",
+            "6",
+        ),
+        (
+            "reduce([\"a\", \"b\"], func(acc, x) { return acc + x; }, \">\")",
+            ">ab",
+        ),
+        ("reduce([], func(acc, x) { return acc + x; }, 10)", "10"),
+    ];
+    for (input, expected) in data {
+        assert_eq!(
+            interpreter.eval_str(input).unwrap().to_string(),
+            expected,
+            "{}",
+            input
+        );
+    }
+
+    for (input, expected) in [
+        ("filter(1, clock)", "`filter` expects a list, not a number."),
+        (
+            "reduce({}, clock, 0)",
+            "`reduce` expects a list, not a map.",
+        ),
+        (
+            "reduce([1], func(x) { return x; }, 0)",
+            "Expected 1 arguments but got 2.",
+        ),
+    ] {
+        let err = interpreter.eval_str(input).unwrap_err();
+        assert!(
+            matches!(err, LoxError::RuntimeError(ref err) if err.message() == expected),
+            "{}: {:?}",
+            input,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_pprint() {
+    let in_out = vec![
+        (
+            "pprint [1, [2, 3]];",
+            "[\n  1,\n  [\n    2,\n    3\n  ]\n]\n",
+        ),
+        (
+            "pprint {\"a\": [\"b\"], \"c\": {}};",
+            "{\n  \"a\": [\n    \"b\"\n  ],\n  \"c\": {}\n}\n",
+        ),
+        ("pprint [];", "[]\n"),
+        ("pprint \"text\";", "text\n"),
+        // the compact form is unchanged
+        ("print [1, [2, 3]];", "[1, [2, 3]]\n"),
+    ];
+    for (src, expected) in in_out {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected, "{}", src);
+    }
+}
+
+#[test]
+fn test_list_reductions() {
+    let mut interpreter = Interpreter::new(std::io::sink());
+    let data = vec![
+        ("sum([1, 2, 3])", 6.0),
+        ("avg([1, 2, 3])", 2.0),
+        ("min([1, 2, 3])", 1.0),
+        ("max([1, 2, 3])", 3.0),
+        ("min([2, -1.5, 3])", -1.5),
+        ("avg([1, 2])", 1.5),
+        ("sum([])", 0.0),
+    ];
+    for (input, expected) in data {
+        assert_eq!(
+            interpreter.eval_str(input).unwrap(),
+            LiteralValue::Num(expected),
+            "{}",
+            input
+        );
+    }
+
+    let errors = vec![
+        ("avg([])", "`avg` expects a non-empty list."),
+        ("min([])", "`min` expects a non-empty list."),
+        ("max([])", "`max` expects a non-empty list."),
+        (
+            "sum([1, \"2\"])",
+            "`sum` expects a list of numbers, not one containing a string.",
+        ),
+        (
+            "max([1, nil])",
+            "`max` expects a list of numbers, not one containing a nil.",
+        ),
+        ("sum(1)", "`sum` expects a list, not a number."),
+    ];
+    for (input, expected) in errors {
+        let err = interpreter.eval_str(input).unwrap_err();
+        assert!(
+            matches!(err, LoxError::RuntimeError(ref err) if err.message() == expected),
+            "{}: {:?}",
+            input,
+            err
+        );
+    }
+}
+
+#[test]
+fn test_try_stmt() {
+    let cases = vec![
+        ("try { print 1; } finally { print 2; }", "1\n2\n"),
+        (
+            "try { print 1 / 0; print 1; } catch (e) { print e; } finally { print 2; }",
+            "Divided by zero is not allowed.\n2\n",
+        ),
+        (
+            "var e = 1; try { missing; } catch (e) { print \"caught\"; } print e;",
+            "caught\n1\n",
+        ),
+        (
+            "func f() { try { return 1; } finally { print \"cleanup\"; } return 2; } print f();",
+            "cleanup\n1\n",
+        ),
+        (
+            "func f() { try { return 1; } finally { return 2; } } print f();",
+            "2\n",
+        ),
+        (
+            "func f() { try { return 1 / 0; } catch (e) { return e; } } print f();",
+            "Divided by zero is not allowed.\n",
+        ),
+    ];
+    for (src, expected) in cases {
+        let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let mut buf = vec![];
+        let mut interpreter = Interpreter::new(&mut buf);
+        interpreter.interpret(&stmts).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected, "{}", src);
+    }
+
+    // without a catch clause the error propagates after the finally block
+    let src = "try { print 1 / 0; } finally { print 2; }";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut buf = vec![];
+    let mut interpreter = Interpreter::new(&mut buf);
+    assert!(interpreter.interpret(&stmts).is_err());
+    assert_eq!(String::from_utf8_lossy(&buf), "2\n");
+}
+
+#[test]
+fn test_string_shared_across_assignment() {
+    let src = "var a = \"a long string\" * 1000; var b = a; var c; c = b;";
+    let tokens = Scanner::new(String::from(src)).scan_tokens().unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    let mut interpreter = Interpreter::new(std::io::sink());
+    interpreter.interpret(&stmts).unwrap();
+
+    let values: Vec<LiteralValue> = ["a", "b", "c"]
+        .iter()
+        .map(|name| interpreter.eval_str(name).unwrap())
+        .collect();
+    let [LiteralValue::Str(a), LiteralValue::Str(b), LiteralValue::Str(c)] = &values[..] else {
+        panic!("expected strings")
+    };
+    assert!(Rc::ptr_eq(a, b) && Rc::ptr_eq(b, c));
+    assert_eq!(values[0], values[2]);
+    assert_eq!(values[0].to_string().len(), 13 * 1000);
+}
@@ -0,0 +1,11 @@
+pub mod environment;
+pub mod error;
+pub mod expression;
+pub mod interpreter;
+pub mod lox;
+pub mod native;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod statement;
+pub mod token;
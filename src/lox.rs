@@ -1,50 +1,121 @@
-use std::io::{self, Write};
+use std::io::{self, BufRead, Write};
 
 use crate::error::{LoxError, ParseError};
 use crate::interpreter::Interpreter;
+use crate::native::Native;
 use crate::parser::Parser;
+use crate::resolver::Resolver;
 use crate::scanner::Scanner;
+use crate::statement::{PrintStmt, Stmt};
+use crate::token::Token;
 use std::fs::File;
 use std::io::Read;
 
+/// language constructs supported by this build, reported by `--features`
+pub const FEATURES: &[&str] = &[
+    "print", "var", "block", "capture", "if", "while", "for", "logical", "function", "ternary",
+    "lambda", "try", "list", "map", "pprint",
+];
+
+/// write the supported language constructs, one per line
+pub fn write_features<W: Write>(output: &mut W) -> io::Result<()> {
+    for feature in FEATURES {
+        writeln!(output, "{}", feature)?;
+    }
+    Ok(())
+}
+
+/// write each token's type, lexeme and line, one token per line
+pub fn write_tokens<W: Write>(output: &mut W, tokens: &[Token]) -> io::Result<()> {
+    for token in tokens {
+        writeln!(
+            output,
+            "{:?} {:?} {}",
+            token.r#type, token.lexeme, token.line
+        )?;
+    }
+    Ok(())
+}
+
+/// write each statement's syntax tree, one top-level statement per line
+pub fn write_ast<W: Write>(output: &mut W, stmts: &[Stmt]) -> io::Result<()> {
+    for stmt in stmts {
+        writeln!(output, "{}", stmt)?;
+    }
+    Ok(())
+}
+
+/// the source of `filename`, or all of stdin without one
+pub fn read_input(filename: Option<&str>) -> Result<String, LoxError> {
+    read_source_file(filename.unwrap_or("-"))
+}
+
+fn read_source(filename: &str) -> io::Result<String> {
+    let mut file = File::open(filename)?;
+    let mut src_code = String::new();
+    file.read_to_string(&mut src_code)?;
+    Ok(src_code)
+}
+
+/// `-` is all of stdin
+fn read_source_file(filename: &str) -> Result<String, LoxError> {
+    if filename == "-" {
+        return read_all(io::stdin());
+    }
+    read_source(filename).map_err(|err| LoxError::Io(filename.to_string(), err))
+}
+
+fn read_all<R: Read>(input: R) -> Result<String, LoxError> {
+    io::read_to_string(input).map_err(|err| LoxError::Io("<stdin>".to_string(), err))
+}
+
 pub struct Lox<W: Write> {
-    had_error: bool,
     interpretor: Interpreter<W>,
 }
 
 impl<W: Write> Lox<W> {
     pub fn new(output: W) -> Self {
-        Self {
-            had_error: false,
-            interpretor: Interpreter::new(output),
-        }
+        Self::with_interpreter(Interpreter::new(output))
+    }
+
+    /// run on an interpreter configured with `Interpreter::builder`
+    pub fn with_interpreter(interpretor: Interpreter<W>) -> Self {
+        Self { interpretor }
     }
 }
 
 impl<W: Write> Lox<W> {
-    /// execute a .lox file
-    /// TODO: error handler
-    pub fn run_file(&mut self, filename: String) {
-        let mut file = File::open(filename).unwrap();
-        let mut src_code = String::new();
-
-        file.read_to_string(&mut src_code).unwrap();
-        self.run(&src_code).unwrap();
-        if self.had_error {
-            return;
-        };
+    /// execute a .lox file, `-` reads the program from stdin
+    pub fn run_file(&mut self, filename: String) -> Result<(), LoxError> {
+        let src_code = read_source_file(&filename)?;
+        self.run(&src_code)
+    }
+
+    /// execute the whole program read from `input` until EOF
+    pub fn run_reader<R: Read>(&mut self, input: R) -> Result<(), LoxError> {
+        let src_code = read_all(input)?;
+        self.run(&src_code)
     }
 
     /// create an interactive shell environment
-    /// TODO: error handler
     pub fn run_prompt(&mut self) {
+        self.run_prompt_from(io::stdin().lock());
+    }
+
+    /// run an interactive shell reading lines from `input` until EOF
+    pub fn run_prompt_from<R: BufRead>(&mut self, mut input: R) {
         loop {
             print!(">>>");
             io::stdout().flush().unwrap();
-            let mut input = String::new();
-            match io::stdin().read_line(&mut input) {
+            let mut line = String::new();
+            match input.read_line(&mut line) {
+                Ok(0) => break,
                 Ok(_) => {
-                    if let Err(err) = self.run(&input) {
+                    if let Some(filename) = line.trim().strip_prefix(".load ") {
+                        self.load(filename.trim());
+                        continue;
+                    }
+                    for err in self.run_repl_line(&line) {
                         err.report();
                     }
                 }
@@ -53,37 +124,128 @@ impl<W: Write> Lox<W> {
         }
     }
 
-    pub fn run(&mut self, source: &str) -> Result<(), LoxError> {
-        let mut scanner = Scanner::new(source.to_string());
-        let tokens = scanner.scan_tokens();
-        let mut parser = Parser::new(tokens);
-        let stmts = parser.parse().unwrap();
-        if !parser.all_parsed() {
-            return Err(LoxError::ParseError(ParseError::new(
-                "not all token parsed".to_string(),
-            )));
+    /// `.load <file>` in the REPL, runs the file in the live environment
+    fn load(&mut self, filename: &str) {
+        match read_source(filename) {
+            Ok(src_code) => {
+                if let Err(err) = self.run(&src_code) {
+                    err.report();
+                }
+            }
+            Err(error) => println!("error: could not load `{}`: {}", filename, error),
         }
-        // execute all statements
-        for stmt in stmts {
-            self.interpretor
-                .execute(&stmt)
-                .map_err(|err| LoxError::RuntimeError(err))?;
+    }
+
+    /// execute one REPL line statement by statement
+    /// a runtime error is collected and the following statements still run,
+    /// a statement failing to resolve is skipped and reported the same way,
+    /// a parse error means nothing on the line runs,
+    /// a bare expression without `;` is printed
+    pub fn run_repl_line(&mut self, line: &str) -> Vec<LoxError> {
+        let tokens = match Scanner::new(line.to_string()).scan_tokens() {
+            Ok(tokens) => tokens,
+            Err(errs) => return vec![LoxError::ScanErrors(errs)],
+        };
+        let stmts = match parse_repl_line(tokens) {
+            Ok(stmts) => stmts,
+            Err(errs) => return vec![LoxError::ParseErrors(errs)],
+        };
+        let resolved = Resolver::new()
+            .globals(self.interpretor.global_names())
+            .resolve_each(&stmts);
+        let mut errors = vec![];
+        for (stmt, resolved) in stmts.iter().zip(resolved) {
+            if let Err(errs) = resolved {
+                errors.push(LoxError::ParseErrors(errs));
+                continue;
+            }
+            if let Err(err) = self.interpretor.execute(stmt) {
+                let fatal = err.is_fatal();
+                errors.push(LoxError::RuntimeError(err));
+                // the rest of the line is not run after a panic
+                if fatal {
+                    break;
+                }
+            }
         }
+        // show everything before the next prompt
+        self.interpretor.flush_output();
+        errors
+    }
+
+    pub fn run(&mut self, source: &str) -> Result<(), LoxError> {
+        // never execute a program that failed to parse
+        let stmts = parse_source_in(source, self.interpretor.global_names())?;
+        // execute all statements
+        self.interpretor
+            .interpret(&stmts)
+            .map_err(LoxError::RuntimeError)?;
 
         // println!("{}", expr.to_string());
         Ok(())
     }
+
+    /// scan and parse `source` reporting every static error, but never execute it
+    pub fn check(&self, source: &str) -> Result<(), Vec<LoxError>> {
+        parse_source_in(source, self.interpretor.global_names())
+            .map(|_| ())
+            .map_err(|err| vec![err])
+    }
+
+    /// `check` a .lox file
+    pub fn check_file(&self, filename: String) -> Result<(), Vec<LoxError>> {
+        let src_code = read_source_file(&filename).map_err(|err| vec![err])?;
+        self.check(&src_code)
+    }
+}
+
+/// scan, parse and resolve a whole program without executing it
+pub fn parse_source(source: &str) -> Result<Vec<Stmt>, LoxError> {
+    let natives = Native::ALL.iter().map(|native| native.name().to_string());
+    parse_source_in(source, natives)
+}
+
+/// `parse_source` for a program run after `globals` are defined
+fn parse_source_in(
+    source: &str,
+    globals: impl IntoIterator<Item = String>,
+) -> Result<Vec<Stmt>, LoxError> {
+    let mut scanner = Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens().map_err(LoxError::ScanErrors)?;
+    let mut parser = Parser::new(tokens);
+    let stmts = parser.parse().map_err(LoxError::ParseErrors)?;
+    if !parser.all_parsed() {
+        return Err(LoxError::ParseErrors(vec![ParseError::new(
+            "not all token parsed".to_string(),
+        )]));
+    }
+    Resolver::new()
+        .globals(globals)
+        .resolve(&stmts)
+        .map_err(LoxError::ParseErrors)?;
+    Ok(stmts)
+}
+
+/// a whole line that is one expression becomes a print of it, otherwise statements
+fn parse_repl_line(tokens: Vec<Token>) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    let mut parser = Parser::new(tokens.clone());
+    if let Ok(expr) = parser.parse_expression() {
+        if parser.all_parsed() {
+            return Ok(vec![Stmt::Print(PrintStmt::new(expr))]);
+        }
+    }
+    Parser::new(tokens).parse()
 }
 
 #[test]
 fn parse_single_expr() {
-    let kvs = vec![
+    let kvs = [
         ("1 + 2", "(+ 1 2)"),
         ("-1 * (-3 + 4)", "(* (- 1) (grouping (+ (- 3) 4)))"),
     ];
 
     for (k, v) in kvs.iter() {
-        let tokens = Scanner::new(k.to_string()).scan_tokens();
+        let tokens = Scanner::new(k.to_string()).scan_tokens().unwrap();
         let mut parser = Parser::new(tokens);
         let expr = parser.parse_expression().unwrap();
         assert_eq!(expr.to_string(), v.to_string());
@@ -105,6 +267,10 @@ fn test_execute_var_print() {
         ("var a = 1; print !a;", "false\n"),
         ("var a = 1; print !!a;", "true\n"),
         ("var a = 0; print a;", "0\n"),
+        ("print !0;", "false\n"),
+        ("print !\"\";", "false\n"),
+        ("print !nil;", "true\n"),
+        ("if (0) print \"zero is truthy\";", "zero is truthy\n"),
     ];
 
     for (src, expected) in in_out {
@@ -126,3 +292,539 @@ fn test_block_execute() {
         assert_eq!(String::from_utf8_lossy(&buf), expected);
     }
 }
+
+#[test]
+fn test_nil_literal() {
+    let in_out = vec![("print nil;", "nil\n"), ("var a = nil; print a;", "nil\n")];
+
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_no_execution_on_parse_error() {
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    let result = lox.run("print 1; var 2; print 3;");
+    assert!(matches!(result, Err(LoxError::ParseErrors(errs)) if errs.len() == 1));
+    assert_eq!(String::from_utf8_lossy(&buf), "");
+}
+
+#[test]
+fn test_write_features() {
+    let mut buf = vec![];
+    write_features(&mut buf).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        "print\nvar\nblock\ncapture\nif\nwhile\nfor\nlogical\nfunction\nternary\nlambda\ntry\nlist\nmap\npprint\n"
+    );
+}
+
+#[test]
+fn test_write_tokens() {
+    let tokens = Scanner::new("var s = \"hi\";\nprint s;".to_string())
+        .scan_tokens()
+        .unwrap();
+    let mut buf = vec![];
+    write_tokens(&mut buf, &tokens).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        concat!(
+            "Var \"var\" 1\n",
+            "Identifier \"s\" 1\n",
+            "Equal \"=\" 1\n",
+            "String \"hi\" 1\n",
+            "Semicolon \";\" 1\n",
+            "Print \"print\" 2\n",
+            "Identifier \"s\" 2\n",
+            "Semicolon \";\" 2\n",
+            "Eof \"\" 2\n",
+        )
+    );
+}
+
+#[test]
+fn test_write_ast() {
+    let stmts = parse_source("print 1 + 2;\nvar a = 1;\n{ a = a * 2; }").unwrap();
+    let mut buf = vec![];
+    write_ast(&mut buf, &stmts).unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&buf),
+        "(print (+ 1 2))\n(var a = 1)\n(block a = (* a 2))\n"
+    );
+}
+
+#[test]
+fn test_grouped_assignment() {
+    let in_out = vec![
+        ("var a = 0; var x = (a = 5); print a; print x;", "5\n5\n"),
+        (
+            "var a = 0; var b = 0; var c = ((a = (b = 1))); print a; print b; print c;",
+            "1\n1\n1\n",
+        ),
+    ];
+
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_typed_var() {
+    let in_out = vec![
+        ("var x: number = 1; x = 2; print x;", "2\n"),
+        ("var s: string = \"a\"; s = \"b\"; print s;", "b\n"),
+        ("var a: any = 1; a = \"one\"; a = true; print a;", "true\n"),
+        ("var a: any; print a; a = 1; print a;", "nil\n1\n"),
+        ("var n: nil; print n;", "nil\n"),
+    ];
+
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+
+    for src in ["var x: number = 1; x = \"one\";", "var b: bool = 1;"] {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        assert!(matches!(lox.run(src), Err(LoxError::RuntimeError(_))));
+    }
+
+    let mut lox = Lox::new(std::io::sink());
+    match lox.run("var x: number;") {
+        Err(LoxError::ParseErrors(errors)) => assert_eq!(
+            errors[0].to_string(),
+            "[line 1] Variable `x` is declared as `number` and needs an initializer."
+        ),
+        _ => panic!("a typed var without an initializer should not parse"),
+    }
+}
+
+#[test]
+fn test_capture_print() {
+    let in_out = vec![
+        (
+            "capture { print \"a\"; print \"b\"; } into s; print s;",
+            "a\nb\n\n",
+        ),
+        (
+            "capture { print 1; capture { print 2; } into inner; print inner; } into outer; print outer;",
+            "1\n2\n\n\n",
+        ),
+    ];
+
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_repl_line_continues_after_error() {
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    let errors = lox.run_repl_line("print 1; bogus; print 2;");
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(&errors[0], LoxError::RuntimeError(err) if err.message().contains("bogus")));
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n2\n");
+
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run_prompt_from("print 1; bogus; print 2;\nprint 3;\n".as_bytes());
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n2\n3\n");
+
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    let errors = lox.run_repl_line("print 1; panic(\"boom\"); print 2;");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n");
+
+    // assigning an undeclared name skips only that statement
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    assert!(matches!(
+        &lox.run_repl_line("print 1; b = 1; print 2;")[..],
+        [LoxError::ParseErrors(errs)] if errs[0].to_string() == "[line 1] Undefined variable `b`. help: declare it first with `var b = ...;`"
+    ));
+    assert!(lox.run_repl_line("var b;").is_empty());
+    assert!(lox.run_repl_line("b = 1;").is_empty());
+    // a function may still assign a global declared later on the line
+    assert!(lox
+        .run_repl_line("func f() { c = 1; } var c = 0; f(); print c;")
+        .is_empty());
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n2\n1\n");
+
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run_prompt_from("print 1; b = 1;\nprint 2;\n".as_bytes());
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n2\n");
+}
+
+#[test]
+fn test_repl_echoes_expressions() {
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    for line in [
+        "1 + 2\n",
+        "var a = 1;\n",
+        "a = a + 1\n",
+        "a;\n",
+        "\"a\" * 2\n",
+        "\n",
+    ] {
+        assert!(lox.run_repl_line(line).is_empty(), "{}", line);
+    }
+    // statements and expressions ending with `;` print nothing
+    assert_eq!(String::from_utf8_lossy(&buf), "3\n2\naa\n");
+
+    let mut lox = Lox::new(std::io::sink());
+    assert!(matches!(
+        &lox.run_repl_line("1 +\n")[..],
+        [LoxError::ParseErrors(_)]
+    ));
+}
+
+#[test]
+fn test_compound_assignment() {
+    let in_out = vec![
+        ("var a = 1; a += 4; print a;", "5\n"),
+        ("var a = 3; a *= 2; print a;", "6\n"),
+        ("var a = 3; a -= 5; print a; a /= 4; print a;", "-2\n-0.5\n"),
+        ("var s = \"ab\"; s += \"c\"; print s;", "abc\n"),
+        ("var i = 0; while (i < 10) i += 3; print i;", "12\n"),
+        ("var a = 1; print a += 1;", "2\n"),
+    ];
+
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected, "{}", src);
+    }
+}
+
+#[test]
+fn test_postfix_increment() {
+    let in_out = vec![
+        ("var a = 1; print a++; print a;", "1\n2\n"),
+        ("var a = 1; print a--; print a;", "1\n0\n"),
+        ("var a = 1; var b = a++ + a++; print b; print a;", "3\n3\n"),
+    ];
+
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+
+    let mut lox = Lox::new(std::io::sink());
+    assert!(matches!(lox.run("1++;"), Err(LoxError::ParseErrors(_))));
+    assert!(matches!(
+        lox.run("var s = \"a\"; s++;"),
+        Err(LoxError::RuntimeError(_))
+    ));
+}
+
+#[test]
+fn test_hex_float_literal() {
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run("print 0x1.8p3; print 0x1p-1 + 1;").unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "12\n1.5\n");
+}
+
+#[test]
+fn test_repl_load() {
+    let path = std::env::temp_dir().join(format!("rlox_load_{}.lox", std::process::id()));
+    std::fs::write(
+        &path,
+        "var loaded = 41;\nfunc f() {\n  return loaded + 1;\n}\n",
+    )
+    .unwrap();
+
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    let input = format!(
+        ".load {}\nprint f();\nloaded = f();\n.load /no/such/file.lox\nprint f();\n",
+        path.display()
+    );
+    lox.run_prompt_from(input.as_bytes());
+    std::fs::remove_file(&path).unwrap();
+    // the loaded function keeps working on the session's globals
+    assert_eq!(String::from_utf8_lossy(&buf), "42\n43\n");
+}
+
+#[test]
+fn test_run_reader() {
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run_reader("var a = 1;\nprint a + 1;\n".as_bytes())
+        .unwrap();
+    assert!(lox.run_reader("".as_bytes()).is_ok());
+    assert!(matches!(
+        lox.run_reader("print ;".as_bytes()),
+        Err(LoxError::ParseErrors(_))
+    ));
+    drop(lox);
+    assert_eq!(String::from_utf8_lossy(&buf), "2\n");
+}
+
+#[test]
+fn test_run_missing_file() {
+    let mut lox = Lox::new(std::io::sink());
+    let err = lox.run_file("/no/such/file.lox".to_string()).unwrap_err();
+    assert!(matches!(err, LoxError::Io(..)));
+    assert_eq!(err.to_string(), "Could not open file: /no/such/file.lox");
+    assert!(lox.check_file("/no/such/file.lox".to_string()).is_err());
+}
+
+#[test]
+fn test_check() {
+    let mut buf = vec![];
+    let lox = Lox::new(&mut buf);
+    assert!(lox.check("var a = 1; print a;").is_ok());
+    let errors = lox.check("print 1; var 2; print 3;").unwrap_err();
+    assert!(matches!(&errors[..], [LoxError::ParseErrors(_)]));
+    drop(lox);
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn test_fib_benchmark() {
+    let src = "
+        func fib(n) {
+            if (n < 2) return n;
+            return fib(n - 2) + fib(n - 1);
+        }
+        var start = clock();
+        print fib(20);
+        print clock() - start < 30;
+    ";
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run(src).unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "6765\ntrue\n");
+}
+
+#[test]
+fn test_number_display() {
+    let in_out = vec![
+        ("print 3.0;", "3\n"),
+        ("print 3.14;", "3.14\n"),
+        ("print 7.0 / 2.0;", "3.5\n"),
+        ("print 10 / 2;", "5\n"),
+        ("print 1 / 3;", "0.3333333333333333\n"),
+        ("print -0.0;", "-0\n"),
+    ];
+
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_comments_in_function_body() {
+    let plain = "func f(a) {
+        var b = a + 1;
+        print b;
+        return b * 2;
+    }
+    print f(1);";
+    let commented = "func f(a) { // takes a number
+        /* the body
+           spans lines */ var b = a + 1;
+        // print it
+        print b; /* inline */ return b * 2; // twice
+    }
+    print f(1); // 4";
+    for src in [plain, commented] {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), "2\n4\n");
+    }
+
+    let src = "func f() {
+        // line 2
+        /* line 3
+           line 4 */
+        return \"a\" - 1;
+    }
+    f();";
+    let mut lox = Lox::new(std::io::sink());
+    let err = lox.run(src).unwrap_err();
+    assert!(
+        matches!(&err, LoxError::RuntimeError(err) if err.line() == 5),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn test_configured_interpreter() {
+    let mut buf = vec![];
+    let interpreter = Interpreter::builder(&mut buf)
+        .max_recursion(5)
+        .bool_as_int_output(true)
+        .build();
+    let mut lox = Lox::with_interpreter(interpreter);
+    let src = "func down(n) { if (n == 0) return true; return down(n - 1); }";
+    lox.run(src).unwrap();
+    lox.run("print down(4);").unwrap();
+    let err = lox.run("down(5);").unwrap_err();
+    assert!(matches!(err, LoxError::RuntimeError(err) if err.message().contains("depth exceeded")));
+    drop(lox);
+    assert_eq!(String::from_utf8_lossy(&buf), "1\n");
+}
+
+#[test]
+fn test_buffered_output_flushed_on_error() {
+    let mut buf = vec![];
+    let interpreter = Interpreter::builder(&mut buf).buffered_output(true).build();
+    let mut lox = Lox::with_interpreter(interpreter);
+    lox.run("for (var i = 0; i < 3000; i++) print i;").unwrap();
+    assert!(lox.run("print \"last\"; nope;").is_err());
+    drop(lox);
+    let expected: String = (0..3000).map(|i| format!("{}\n", i)).collect();
+    assert_eq!(String::from_utf8_lossy(&buf), expected + "last\n");
+}
+
+#[test]
+fn test_self_referential_initializer() {
+    let lox = Lox::new(std::io::sink());
+    let err = lox.check("{ var a = a; print 1; }").unwrap_err();
+    assert_eq!(
+        err[0].to_string(),
+        "Error: [line 1] Can't read local variable 'a' in its own initializer."
+    );
+    assert!(lox.check("var a = 1; func f() { var a = a + 1; }").is_err());
+
+    let in_out = vec![
+        ("var a = 1; { var a = 2; print a; } print a;", "2\n1\n"),
+        ("var a = 1; { var b = a + 1; print b; }", "2\n"),
+        ("var a = 1; var a = a + 1; print a;", "2\n"),
+    ];
+    for (src, expected) in in_out {
+        let mut buf = vec![];
+        let mut lox = Lox::new(&mut buf);
+        lox.run(src).unwrap();
+        assert_eq!(String::from_utf8_lossy(&buf), expected);
+    }
+}
+
+#[test]
+fn test_var_without_initializer() {
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run("var a; print a; a = 1; print a;").unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "nil\n1\n");
+
+    let lox = Lox::new(std::io::sink());
+    assert!(lox.check("var a").is_err());
+    assert!(lox.check("var a print a;").is_err());
+}
+
+#[test]
+fn test_for_variable_scope() {
+    let mut lox = Lox::new(std::io::sink());
+    let result = lox.run("for (var i = 0; i < 3; i = i + 1) {} print i;");
+    assert!(
+        matches!(result, Err(LoxError::RuntimeError(err)) if err.message() == "Undefined variable `i`.")
+    );
+
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run("var i = 10; for (var i = 0; i < 2; i = i + 1) print i; print i;")
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "0\n1\n10\n");
+}
+
+#[test]
+fn test_scan_errors_stop_run() {
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    let result = lox.run("print 1;\nprint 2 @ 3;");
+    assert!(
+        matches!(result, Err(LoxError::ScanErrors(ref errs)) if errs.len() == 1 && errs[0].line() == 2)
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Error: [line 2] Invalid token: @"
+    );
+    drop(lox);
+    assert_eq!(String::from_utf8_lossy(&buf), "");
+}
+
+#[test]
+fn test_nil_operand() {
+    let data = vec![
+        ("var a = nil;\na + 1;", "[line 2] Operand to '+' is nil."),
+        ("var a;\n1 <\na;", "[line 2] Operand to '<' is nil."),
+        ("b + 1;", "[line 1] Undefined variable `b`."),
+    ];
+    for (src, expected) in data {
+        let mut lox = Lox::new(std::io::sink());
+        let result = lox.run(src);
+        assert!(
+            matches!(result, Err(LoxError::RuntimeError(ref err)) if err.to_string() == expected),
+            "{}",
+            src
+        );
+    }
+
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run("var a; print a == nil; print a != 1;").unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "true\ntrue\n");
+}
+
+#[test]
+fn test_resolved_closure_binding() {
+    let src = "
+        var a = \"global\";
+        {
+            func show() { print a; }
+            show();
+            var a = \"block\";
+            show();
+            print a;
+        }
+    ";
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run(src).unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "global\nglobal\nblock\n");
+}
+
+#[test]
+fn test_redeclaration() {
+    let mut lox = Lox::new(std::io::sink());
+    let result = lox.run("{\n  var a = 1;\n  var a = 2;\n}");
+    assert!(matches!(
+        result,
+        Err(LoxError::RuntimeError(ref err)) if err.to_string() == "[line 3] Variable `a` is already declared in this scope."
+    ));
+    let mut lox = Lox::new(std::io::sink());
+    assert!(lox.run("func f(a, a) {} f(1, 2);").is_err());
+
+    let mut buf = vec![];
+    let mut lox = Lox::new(&mut buf);
+    lox.run("var a = 1; var a = 2; print a; { var a = 3; print a; } while (a < 4) { var b = a; a = b + 1; }")
+        .unwrap();
+    assert_eq!(String::from_utf8_lossy(&buf), "2\n3\n");
+}
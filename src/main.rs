@@ -1,24 +1,65 @@
 mod args;
-mod environment;
-mod error;
-mod expression;
-mod interpreter;
-mod lox;
-mod parser;
-mod scanner;
-mod statement;
-mod token;
 use args::Args;
 use clap::Parser;
-use error::LoxError;
-use lox::Lox;
+use rlox::error::LoxError;
+use rlox::interpreter::Interpreter;
+use rlox::lox::{self, Lox};
+use rlox::scanner::Scanner;
 
 fn main() -> Result<(), LoxError> {
     let cli = Args::parse();
-    let mut lox = Lox::new(std::io::stdout());
-    match cli.file {
-        Some(filename) => lox.run_file(filename),
-        None => lox.run_prompt(),
+    if cli.features {
+        lox::write_features(&mut std::io::stdout()).unwrap();
+        return Ok(());
+    }
+    if cli.tokens {
+        let tokens = lox::read_input(cli.file.as_deref()).and_then(|source| {
+            Scanner::new(source)
+                .scan_tokens()
+                .map_err(LoxError::ScanErrors)
+        });
+        match tokens {
+            Ok(tokens) => lox::write_tokens(&mut std::io::stdout(), &tokens).unwrap(),
+            Err(err) => {
+                err.report();
+                std::process::exit(err.exit_code());
+            }
+        }
+        return Ok(());
+    }
+    if cli.ast {
+        match lox::read_input(cli.file.as_deref()).and_then(|source| lox::parse_source(&source)) {
+            Ok(stmts) => lox::write_ast(&mut std::io::stdout(), &stmts).unwrap(),
+            Err(err) => {
+                err.report();
+                std::process::exit(err.exit_code());
+            }
+        }
+        return Ok(());
+    }
+    let interpreter = Interpreter::builder(std::io::stdout())
+        .trace(cli.trace)
+        .build();
+    let mut lox = Lox::with_interpreter(interpreter);
+    if cli.check {
+        let filename = cli.file.expect("--check requires a file");
+        if let Err(errors) = lox.check_file(filename) {
+            errors.iter().for_each(|err| err.report());
+            std::process::exit(errors[0].exit_code());
+        }
+        return Ok(());
+    }
+    let result = match (cli.eval, cli.file) {
+        (Some(program), _) => lox.run(&program),
+        (None, Some(filename)) => lox.run_file(filename),
+        (None, None) => {
+            lox.run_prompt();
+            Ok(())
+        }
+    };
+    if let Err(err) = result {
+        err.report();
+        std::process::exit(err.exit_code());
     }
     Ok(())
 }
@@ -0,0 +1,149 @@
+use std::ops::RangeInclusive;
+
+/// functions implemented by the interpreter, defined in the global scope
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Native {
+    /// `env(name)`, the environment variable as a string or `nil` if unset
+    Env,
+    /// `exists(name)`, whether a variable called `name` is defined in scope
+    Exists,
+    /// `write_file(path, contents)`, only under `Interpreter::allow_fs_write`
+    WriteFile,
+    /// `read_file(path)`, the contents as a string, only under `Interpreter::allow_fs_read`
+    ReadFile,
+    /// `round(n)` or `round(n, digits)`, halves round away from zero
+    Round,
+    /// `between(x, lo, hi)`, whether `lo <= x <= hi` for three numbers or three strings
+    Between,
+    /// `group_digits(n)`, `n` as text with its whole part grouped by thousands like `"1,234.5"`
+    GroupDigits,
+    /// `clock()`, seconds since the Unix epoch
+    Clock,
+    /// `sleep(seconds)`, pauses the program and returns `nil`
+    Sleep,
+    /// `time(fn)`, calls `fn` without arguments and returns the seconds it took
+    Time,
+    /// `pad_left(s, width, fill)`, `fill` is one character and defaults to a space
+    PadLeft,
+    /// `pad_right(s, width, fill)`, like `pad_left` but pads after `s`
+    PadRight,
+    /// `replace(s, from, to)`, `s` with every `from` replaced by `to`, unchanged if `from` is empty
+    Replace,
+    /// `version()`, the version of this interpreter like `"0.1.0"`
+    Version,
+    /// `panic(msg)`, aborts the program even under `Interpreter::continue_on_error`
+    Panic,
+    /// `assert_throws(fn)`, calls `fn` without arguments and fails unless it raises
+    /// a runtime error, a `panic` is never caught
+    AssertThrows,
+    /// `sum(list)`, the total of a list of numbers, `0` for an empty list
+    Sum,
+    /// `avg(list)`, the mean of a non-empty list of numbers
+    Avg,
+    /// `min(list)`, the smallest of a non-empty list of numbers
+    Min,
+    /// `max(list)`, the largest of a non-empty list of numbers
+    Max,
+    /// `keys(map)`, the keys of `map` as a list in insertion order
+    Keys,
+    /// `values(map)`, the values of `map` as a list in insertion order
+    Values,
+    /// `map(list, fn)`, a new list of `fn(element)` for each element
+    Map,
+    /// `filter(list, predicate)`, a new list of the elements `predicate` is truthy for
+    Filter,
+    /// `reduce(list, fn, initial)`, folds from the left with `fn(acc, element)`
+    Reduce,
+}
+
+impl Native {
+    pub const ALL: &'static [Native] = &[
+        Native::Env,
+        Native::Exists,
+        Native::WriteFile,
+        Native::ReadFile,
+        Native::Round,
+        Native::Between,
+        Native::GroupDigits,
+        Native::Clock,
+        Native::Sleep,
+        Native::Time,
+        Native::PadLeft,
+        Native::PadRight,
+        Native::Replace,
+        Native::Version,
+        Native::Panic,
+        Native::AssertThrows,
+        Native::Sum,
+        Native::Avg,
+        Native::Min,
+        Native::Max,
+        Native::Keys,
+        Native::Values,
+        Native::Map,
+        Native::Filter,
+        Native::Reduce,
+    ];
+
+    /// the global name the native is bound to
+    pub fn name(&self) -> &'static str {
+        match self {
+            Native::Env => "env",
+            Native::Exists => "exists",
+            Native::WriteFile => "write_file",
+            Native::ReadFile => "read_file",
+            Native::Round => "round",
+            Native::Between => "between",
+            Native::GroupDigits => "group_digits",
+            Native::Clock => "clock",
+            Native::Sleep => "sleep",
+            Native::Time => "time",
+            Native::PadLeft => "pad_left",
+            Native::PadRight => "pad_right",
+            Native::Replace => "replace",
+            Native::Version => "version",
+            Native::Panic => "panic",
+            Native::AssertThrows => "assert_throws",
+            Native::Sum => "sum",
+            Native::Avg => "avg",
+            Native::Min => "min",
+            Native::Max => "max",
+            Native::Keys => "keys",
+            Native::Values => "values",
+            Native::Map => "map",
+            Native::Filter => "filter",
+            Native::Reduce => "reduce",
+        }
+    }
+
+    /// the numbers of arguments accepted, trailing ones may be optional
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        match self {
+            Native::Env
+            | Native::Exists
+            | Native::GroupDigits
+            | Native::ReadFile
+            | Native::Sleep
+            | Native::Time
+            | Native::Panic
+            | Native::AssertThrows
+            | Native::Sum
+            | Native::Avg
+            | Native::Min
+            | Native::Max
+            | Native::Keys
+            | Native::Values => 1..=1,
+            Native::WriteFile | Native::Map | Native::Filter => 2..=2,
+            Native::Round => 1..=2,
+            Native::Between | Native::Replace | Native::Reduce => 3..=3,
+            Native::Clock | Native::Version => 0..=0,
+            Native::PadLeft | Native::PadRight => 2..=3,
+        }
+    }
+}
+
+impl std::fmt::Display for Native {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<native fn {}>", self.name())
+    }
+}
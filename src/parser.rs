@@ -1,32 +1,76 @@
 // program        → declaration * EOF ;
-// declaration    → varDecl
+// declaration    → funDecl
+//                | varDecl
 //                | statement ;
-// varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+// funDecl        → "func" function ;
+// function       → IDENTIFIER "(" parameters? ")" block ;
+// parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+// varDecl        → "var" IDENTIFIER ( ":" type )? ( "=" expression )? ";" ;
+// type           → "number" | "string" | "bool" | "nil" | "any" ;
 // statement      → exprStmt
+//                | forStmt
+//                | ifStmt
+//                | unlessStmt
 //                | printStmt
-//                | block;
+//                | returnStmt
+//                | whileStmt
+//                | untilStmt
+//                | captureStmt
+//                | tryStmt
+//                | block ;
+// forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+//                  expression? ";"
+//                  expression? ")" statement ;
+// ifStmt         → "if" "(" expression ")" statement
+//                  ( "elif" "(" expression ")" statement )*
+//                  ( "else" statement )? ;
+// unlessStmt     → "unless" "(" expression ")" statement ;
+// whileStmt      → "while" "(" expression ")" statement ;
+// untilStmt      → "until" "(" expression ")" statement ;
 // block          → "{" declaration* "}" ;
+// captureStmt    → "capture" block "into" IDENTIFIER ";" ;
+// tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )?
+//                  ( "finally" block )? ;
 // exprStmt       → expression ";" ;
-// printStmt      → "print" expression ";" ;
+// printStmt      → ( "print" | "pprint" ) expression ";" ;
+// returnStmt     → "return" expression? ";" ;
 // expression     → assignment ;
-// assignment     → IDENTIFIRE "=" assignment
-//                | equality;
+// assignment     → IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" ) assignment
+//                | ternary ;
+// ternary        → logic_or ( "?" expression ":" ternary )? ;
+// logic_or       → logic_and ( "or" logic_and )* ;
+// logic_and      → equality ( "and" equality )* ;
 // equality       → comparison ( ( "!=" | "==" ) comparison )* ;
 // comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
 // term           → factor ( ( "-" | "+" ) factor )* ;
-// factor         → unary ( ( "/" | "*" ) unary )* ;
+// factor         → unary ( ( "/" | "*" | "%" ) unary )* ;
 // unary          → ( "!" | "-" ) unary
-//                | primary ;
+//                | postfix ;
+// postfix        → IDENTIFIER ( "++" | "--" )
+//                | call ;
+// call           → primary ( "(" arguments? ")" | "[" expression "]" )* ;
+// arguments      → expression ( "," expression )* ;
 // primary        → NUMBER | STRING | "true" | "false" | "nil"
 //                | "(" expression ")"
-//                | IDENTIFIER ;
+//                | IDENTIFIER
+//                | "func" "(" parameters? ")" block
+//                | "[" arguments? "]"
+//                | "{" entries? "}" ;
+// entries        → expression ":" expression ( "," expression ":" expression )* ;
+
+use std::{cell::Cell, rc::Rc};
 
 use crate::{
     error::ParseError,
     expression::{
-        AssignExpr, BinaryExpr, Expr, GroupingExpr, LiteralExpr, UnaryExpr, VariableExpr,
+        AssignExpr, BinaryExpr, CallExpr, Expr, FunctionExpr, GroupingExpr, IndexExpr, ListExpr,
+        LiteralExpr, LiteralValue, LogicalExpr, MapExpr, PostfixExpr, TernaryExpr, UnaryExpr,
+        VariableExpr,
+    },
+    statement::{
+        Block, CaptureStmt, CatchClause, ExprStmt, FunctionStmt, IfStmt, PrintStmt, ReturnStmt,
+        Stmt, TryStmt, TypeAnnotation, VarDecStmt, WhileStmt,
     },
-    statement::{Block, ExprStmt, PrintStmt, Stmt, VarDecStmt},
     token::Token,
     token::TokenType,
 };
@@ -34,91 +78,393 @@ use crate::{
 pub struct Parser {
     pub tokens: Vec<Token>,
     current: usize,
+    /// the `(`, `{` and `[` not closed yet, the innermost is last
+    open_delimiters: Vec<Token>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, current: 0 }
+        Self {
+            tokens,
+            current: 0,
+            open_delimiters: vec![],
+        }
     }
 
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
+    /// parse only `tokens[start..end]` of an already scanned source, e.g. to reparse
+    /// one changed statement; the range ends at an Eof placed at the next token.
+    /// an `end` past the tokens is clamped to their end and a `start` past `end` to `end`,
+    /// an empty range parses as an empty program
+    pub fn from_range(tokens: &[Token], start: usize, end: usize) -> Self {
+        let end = end.min(tokens.len());
+        let start = start.min(end);
+        let mut range = tokens[start..end].to_vec();
+        let (line, column) = tokens
+            .get(end)
+            .or(tokens.last())
+            .map_or((1, 1), |next| (next.line, next.column));
+        range.push(Token::new(TokenType::Eof, String::new(), line, column));
+        Self::new(range)
+    }
+
+    /// parse the whole program, collecting every error instead of
+    /// returning a partial list of statements
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<ParseError>> {
         let mut statements = vec![];
+        let mut errors = vec![];
         while !self.is_at_end() {
-            // TODO: engage all the parse errors
             match self.declaration() {
                 Ok(statement) => statements.push(statement),
-                Err(_) => self.synchronize(),
+                Err(err) => {
+                    errors.push(err);
+                    self.synchronize();
+                }
             }
         }
-        Ok(statements)
+        if errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(errors)
+        }
     }
 
     pub fn all_parsed(&self) -> bool {
         self.current == self.tokens.len() - 1
     }
 
-    // Make test easier
+    /// parse a single expression, the tokens after it are left unparsed
     pub fn parse_expression(&mut self) -> Result<Expr, ParseError> {
         self.expression()
     }
 }
 
 impl Parser {
-    // block          → "{" declaration* "}" ;
+    /// block          → "{" declaration* "}" ;
     fn block(&mut self) -> Result<Stmt, ParseError> {
+        Ok(Stmt::Block(self.block_body()?))
+    }
+
+    /// the declarations of a block whose `{` is already consumed
+    fn block_body(&mut self) -> Result<Block, ParseError> {
+        let line = self.previous().line;
+        self.open_delimiter();
+        let stmts = self.block_declarations()?;
+        self.close_delimiter(TokenType::RightBrace)?;
+
+        Ok(Block::new(stmts, line))
+    }
+
+    fn block_declarations(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut stmts = vec![];
         // not } or end meaning still in the block
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
             stmts.push(self.declaration()?);
         }
-        self.consume(TokenType::RightBrace);
+        Ok(stmts)
+    }
+
+    /// captureStmt    → "capture" block "into" IDENTIFIER ";" ;
+    fn capture_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let line = self.previous().line;
+        self.consume(TokenType::LeftBrace)?;
+        let body = self.block_body()?;
+        self.consume(TokenType::Into)?;
+        let var_name = self.consume(TokenType::Identifier)?.lexeme.clone();
+        self.consume(TokenType::Semicolon)?;
+        Ok(Stmt::Capture(CaptureStmt::new(body, var_name, line)))
+    }
 
-        Ok(Stmt::Block(Block::new(stmts)))
+    /// tryStmt        → "try" block ( "catch" "(" IDENTIFIER ")" block )?
+    ///                  ( "finally" block )? ;
+    /// a `try` needs at least one of the clauses
+    fn try_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftBrace)?;
+        let body = self.block_body()?;
+        let catch = if self.match_one(TokenType::Catch) {
+            self.consume(TokenType::LeftParen)?;
+            self.open_delimiter();
+            let var_name = self.consume(TokenType::Identifier)?.lexeme.clone();
+            self.close_delimiter(TokenType::RightParen)?;
+            self.consume(TokenType::LeftBrace)?;
+            Some(CatchClause {
+                var_name,
+                body: self.block_body()?,
+            })
+        } else {
+            None
+        };
+        let finally = if self.match_one(TokenType::Finally) {
+            self.consume(TokenType::LeftBrace)?;
+            Some(self.block_body()?)
+        } else {
+            None
+        };
+        if catch.is_none() && finally.is_none() {
+            return Err(ParseError::new(format!(
+                "[line {}:{}] Expected `catch` or `finally` after the `try` block",
+                keyword.line, keyword.column
+            )));
+        }
+        Ok(Stmt::Try(TryStmt::new(body, catch, finally, keyword.line)))
     }
 
-    // declaration    → varDecl
-    //                | statement ;
+    /// declaration    → funDecl
+    ///                | varDecl
+    ///                | statement ;
     fn declaration(&mut self) -> Result<Stmt, ParseError> {
-        if self.token_type_match(&vec![TokenType::Var]) {
+        // `func` without a name starts an expression statement
+        if self.check(TokenType::Func) && self.check_next(TokenType::Identifier) {
+            self.advance();
+            self.function()
+        } else if self.match_one(TokenType::Var) {
             self.var_declaration()
         } else {
             self.statement()
         }
     }
 
-    // varDecl        → "var" IDENTIFIER ( "=" expression )? ";" ;
+    /// function       → IDENTIFIER "(" parameters? ")" block ;
+    fn function(&mut self) -> Result<Stmt, ParseError> {
+        let name = self.consume(TokenType::Identifier)?.clone();
+        let declaration = self.function_rest(Some(name.lexeme), name.line)?;
+        Ok(Stmt::Function(Rc::new(declaration)))
+    }
+
+    /// the part after the name
+    ///                → "(" parameters? ")" block ;
+    /// parameters     → IDENTIFIER ( "," IDENTIFIER )* ;
+    fn function_rest(
+        &mut self,
+        name: Option<String>,
+        line: usize,
+    ) -> Result<FunctionStmt, ParseError> {
+        self.consume(TokenType::LeftParen)?;
+        self.open_delimiter();
+        let mut params = vec![];
+        if !self.check(TokenType::RightParen) {
+            loop {
+                params.push(self.consume(TokenType::Identifier)?.lexeme.clone());
+                if !self.match_one(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        self.close_delimiter(TokenType::RightParen)?;
+        self.consume(TokenType::LeftBrace)?;
+        let body = self.block_body()?;
+        Ok(FunctionStmt::new(name, params, body, line))
+    }
+
+    /// varDecl        → "var" IDENTIFIER ( ":" type )? ( "=" expression )? ";" ;
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
-        let var_name = self.consume(TokenType::Identifier)?.lexeme.clone();
+        let name = self.consume(TokenType::Identifier)?.clone();
+        let mut annotation = None;
+        if self.match_one(TokenType::Colon) {
+            annotation = Some(self.type_annotation()?);
+        }
         let mut expr: Option<Expr> = None;
-        if self.token_type_match(&vec![TokenType::Equal]) {
+        if self.match_one(TokenType::Equal) {
             expr = Some(self.expression()?);
-            self.consume(TokenType::Semicolon)?;
         }
-        Ok(Stmt::Var(VarDecStmt::new(var_name, expr)))
+        // the implicit `nil` would break the annotation before the first assignment
+        if let (Some(annotation), None) = (annotation, &expr) {
+            if !annotation.accepts(&LiteralValue::Nil) {
+                return Err(ParseError::new(format!(
+                    "[line {}] Variable `{}` is declared as `{}` and needs an initializer.",
+                    name.line, name.lexeme, annotation
+                )));
+            }
+        }
+        self.consume(TokenType::Semicolon)?;
+        Ok(Stmt::Var(VarDecStmt::new(
+            name.lexeme,
+            expr,
+            annotation,
+            name.line,
+        )))
+    }
+
+    /// type           → "number" | "string" | "bool" | "nil" | "any" ;
+    fn type_annotation(&mut self) -> Result<TypeAnnotation, ParseError> {
+        let token = self.advance().clone();
+        TypeAnnotation::from_name(&token.lexeme).ok_or_else(|| {
+            ParseError::new(format!(
+                "[line {}] Unknown type `{}`, expected one of number, string, bool, nil, any",
+                token.line, token.lexeme
+            ))
+        })
     }
 
     /// statement      → exprStmt
+    ///                | forStmt
+    ///                | ifStmt
+    ///                | unlessStmt
     ///                | printStmt
+    ///                | returnStmt
+    ///                | whileStmt
+    ///                | untilStmt
+    ///                | captureStmt
+    ///                | tryStmt
     ///                | block ;
     fn statement(&mut self) -> Result<Stmt, ParseError> {
-        if self.token_type_match(&vec![TokenType::Print]) {
+        if self.match_one(TokenType::For) {
+            self.for_stmt()
+        } else if self.match_one(TokenType::If) {
+            self.if_stmt()
+        } else if self.match_one(TokenType::Unless) {
+            self.unless_stmt()
+        } else if self.match_one(TokenType::While) {
+            self.while_stmt()
+        } else if self.match_one(TokenType::Until) {
+            self.until_stmt()
+        } else if self.token_type_match(&[TokenType::Print, TokenType::Pprint]) {
             self.print_stmt()
-        } else if self.token_type_match(&vec![TokenType::LeftBrace]) {
+        } else if self.match_one(TokenType::Return) {
+            self.return_stmt()
+        } else if self.match_one(TokenType::Capture) {
+            self.capture_stmt()
+        } else if self.match_one(TokenType::Try) {
+            self.try_stmt()
+        } else if self.match_one(TokenType::LeftBrace) {
             self.block()
         } else {
             self.expr_stmt()
         }
     }
 
-    /// printStmt      → "print" expression ";" ;
+    /// ifStmt         → "if" "(" expression ")" statement
+    ///                  ( "elif" "(" expression ")" statement )*
+    ///                  ( "else" statement )? ;
+    /// the else binds to the nearest if, an elif is an if nested in the else branch
+    fn if_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let condition = self.condition()?;
+        let then_branch = self.statement()?;
+        let else_branch = if self.match_one(TokenType::Elif) {
+            Some(self.if_stmt()?)
+        } else if self.match_one(TokenType::Else) {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+        Ok(Stmt::If(IfStmt::new(condition, then_branch, else_branch)))
+    }
+
+    /// unlessStmt     → "unless" "(" expression ")" statement ;
+    /// desugared into `if (!(condition)) statement`
+    fn unless_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let condition = negate(&keyword, self.condition()?);
+        let then_branch = self.statement()?;
+        Ok(Stmt::If(IfStmt::new(condition, then_branch, None)))
+    }
+
+    /// whileStmt      → "while" "(" expression ")" statement ;
+    fn while_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let condition = self.condition()?;
+        let body = self.statement()?;
+        Ok(Stmt::While(WhileStmt::new(condition, body)))
+    }
+
+    /// untilStmt      → "until" "(" expression ")" statement ;
+    /// desugared into `while (!(condition)) statement`
+    fn until_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let condition = negate(&keyword, self.condition()?);
+        let body = self.statement()?;
+        Ok(Stmt::While(WhileStmt::new(condition, body)))
+    }
+
+    /// the parenthesized condition of `if`, `while` and their sugar
+    fn condition(&mut self) -> Result<Expr, ParseError> {
+        self.consume(TokenType::LeftParen)?;
+        self.open_delimiter();
+        let condition = self.expression()?;
+        self.close_delimiter(TokenType::RightParen)?;
+        // extra parentheses are the way to say the assignment is intended
+        if let Expr::Assign(assign) = &condition {
+            return Err(ParseError::new(format!(
+                "[line {}:{}] Assignment to `{}` in a condition, did you mean `==`? help: wrap it in parentheses if the assignment is intended",
+                assign.lvar.line, assign.lvar.column, assign.lvar.lexeme
+            )));
+        }
+        Ok(condition)
+    }
+
+    /// forStmt        → "for" "(" ( varDecl | exprStmt | ";" )
+    ///                  expression? ";"
+    ///                  expression? ")" statement ;
+    /// desugared into `{ initializer; while (condition) { body; increment; } }`
+    fn for_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let line = self.previous().line;
+        self.consume(TokenType::LeftParen)?;
+        self.open_delimiter();
+        let initializer = if self.match_one(TokenType::Semicolon) {
+            None
+        } else if self.match_one(TokenType::Var) {
+            Some(self.var_declaration()?)
+        } else {
+            Some(self.expr_stmt()?)
+        };
+        let condition = if self.check(TokenType::Semicolon) {
+            // no condition means loop forever
+            let token = self.peek();
+            Expr::Literal(LiteralExpr {
+                token: Token::new(
+                    TokenType::True,
+                    "true".to_string(),
+                    token.line,
+                    token.column,
+                ),
+            })
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::Semicolon)?;
+        let increment = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        self.close_delimiter(TokenType::RightParen)?;
+
+        let mut body = self.statement()?;
+        if let Some(increment) = increment {
+            let line = increment.line();
+            body = Stmt::Block(Block::new(
+                vec![body, Stmt::Expr(ExprStmt::new(increment))],
+                line,
+            ));
+        }
+        let mut stmts = initializer.into_iter().collect::<Vec<_>>();
+        stmts.push(Stmt::While(WhileStmt::new(condition, body)));
+        Ok(Stmt::Block(Block::new(stmts, line)))
+    }
+
+    /// printStmt      → ( "print" | "pprint" ) expression ";" ;
     fn print_stmt(&mut self) -> Result<Stmt, ParseError> {
-        let stmt = self
-            .expression()
-            .map(|expr| Stmt::Print(PrintStmt::new(expr)))?;
+        let pretty = self.previous().r#type == TokenType::Pprint;
+        let expr = self.expression()?;
+        let stmt = Stmt::Print(if pretty {
+            PrintStmt::pretty(expr)
+        } else {
+            PrintStmt::new(expr)
+        });
         self.consume(TokenType::Semicolon)?;
         Ok(stmt)
     }
 
+    /// returnStmt     → "return" expression? ";" ;
+    fn return_stmt(&mut self) -> Result<Stmt, ParseError> {
+        let line = self.previous().line;
+        let mut value = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(self.expression()?);
+        }
+        self.consume(TokenType::Semicolon)?;
+        Ok(Stmt::Return(ReturnStmt::new(value, line)))
+    }
+
     /// exprStmt       → expression ";" ;
     fn expr_stmt(&mut self) -> Result<Stmt, ParseError> {
         let stmt = self
@@ -128,15 +474,18 @@ impl Parser {
         Ok(stmt)
     }
 
-    /// expression     → equality ;
+    /// expression     → assignment ;
     fn expression(&mut self) -> Result<Expr, ParseError> {
         self.assignment()
     }
 
+    /// assignment     → IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" ) assignment
+    ///                | ternary ;
+    /// the compound operators are desugared into `name = name op value`
     fn assignment(&mut self) -> Result<Expr, ParseError> {
-        let expr = self.equality()?;
+        let expr = self.ternary()?;
         // assignment statement
-        if self.token_type_match(&vec![TokenType::Equal]) {
+        if self.match_one(TokenType::Equal) {
             let equals = self.previous().to_owned();
             let value = self.assignment()?;
             if let Expr::Variable(var_expr) = expr {
@@ -144,6 +493,7 @@ impl Parser {
                 return Ok(Expr::Assign(AssignExpr {
                     lvar: token,
                     value: Box::new(value),
+                    depth: Cell::new(None),
                 }));
             }
             // TODO: more detail error
@@ -152,13 +502,90 @@ impl Parser {
                 equals
             )));
         }
+        // `a += b` is sugar for `a = a + b`
+        if self.token_type_match(&[
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            let compound = self.previous().to_owned();
+            let value = self.assignment()?;
+            let Expr::Variable(var_expr) = expr else {
+                return Err(ParseError::new(format!(
+                    "[line {}:{}] Invalid target `{}` for `{}`.",
+                    compound.line, compound.column, expr, compound.lexeme
+                )));
+            };
+            let (r#type, lexeme) = match compound.r#type {
+                TokenType::PlusEqual => (TokenType::Plus, "+"),
+                TokenType::MinusEqual => (TokenType::Minus, "-"),
+                TokenType::StarEqual => (TokenType::Star, "*"),
+                _ => (TokenType::Slash, "/"),
+            };
+            let operator = Token::new(r#type, lexeme.to_string(), compound.line, compound.column);
+            return Ok(Expr::Assign(AssignExpr {
+                lvar: var_expr.var.clone(),
+                value: Box::new(Expr::Binary(BinaryExpr {
+                    left: Box::new(Expr::Variable(var_expr)),
+                    operator,
+                    right: Box::new(value),
+                })),
+                depth: Cell::new(None),
+            }));
+        }
+        Ok(expr)
+    }
+    /// ternary        → logic_or ( "?" expression ":" ternary )? ;
+    fn ternary(&mut self) -> Result<Expr, ParseError> {
+        let condition = self.logic_or()?;
+        if !self.match_one(TokenType::Question) {
+            return Ok(condition);
+        }
+        let then_branch = self.expression()?;
+        self.consume(TokenType::Colon)?;
+        let else_branch = self.ternary()?;
+        Ok(Expr::Ternary(TernaryExpr {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch: Box::new(else_branch),
+        }))
+    }
+
+    /// logic_or       → logic_and ( "or" logic_and )* ;
+    fn logic_or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.logic_and()?;
+        while self.match_one(TokenType::Or) {
+            let op = self.previous().clone();
+            let right = self.logic_and()?;
+            expr = Expr::Logical(LogicalExpr {
+                left: Box::new(expr),
+                operator: op,
+                right: Box::new(right),
+            });
+        }
         Ok(expr)
     }
+
+    /// logic_and      → equality ( "and" equality )* ;
+    fn logic_and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.equality()?;
+        while self.match_one(TokenType::And) {
+            let op = self.previous().clone();
+            let right = self.equality()?;
+            expr = Expr::Logical(LogicalExpr {
+                left: Box::new(expr),
+                operator: op,
+                right: Box::new(right),
+            });
+        }
+        Ok(expr)
+    }
+
     /// equality       → comparison ( ( "!=" | "==" ) comparison )* ;
     fn equality(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.comparison()?;
-        let op_types = vec![TokenType::BangEqual, TokenType::EqualEqual];
-        while self.token_type_match(&op_types) {
+        while self.token_type_match(&[TokenType::BangEqual, TokenType::EqualEqual]) {
             let op = self.previous().clone();
             let right = self.comparison()?;
             expr = Expr::Binary(BinaryExpr {
@@ -170,10 +597,10 @@ impl Parser {
         Ok(expr)
     }
 
-    // comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+    /// comparison     → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
     fn comparison(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.term()?;
-        let op_types = vec![
+        let op_types = [
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
@@ -192,12 +619,10 @@ impl Parser {
         Ok(expr)
     }
 
-    // term           → factor ( ( "-" | "+" ) factor )* ;
+    /// term           → factor ( ( "-" | "+" ) factor )* ;
     fn term(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.factor()?;
-        let op_types = vec![TokenType::Minus, TokenType::Plus];
-
-        while self.token_type_match(&op_types) {
+        while self.token_type_match(&[TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous().clone();
             let right = self.factor()?;
             expr = Expr::Binary(BinaryExpr {
@@ -209,12 +634,10 @@ impl Parser {
         Ok(expr)
     }
 
-    // factor         → unary ( ( "/" | "*" ) unary )* ;
+    /// factor         → unary ( ( "/" | "*" | "%" ) unary )* ;
     fn factor(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.unary()?;
-        let op_types = vec![TokenType::Slash, TokenType::Star];
-
-        while self.token_type_match(&op_types) {
+        while self.token_type_match(&[TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
             expr = Expr::Binary(BinaryExpr {
@@ -226,11 +649,10 @@ impl Parser {
         Ok(expr)
     }
 
-    // unary          → ( "!" | "-" ) unary
-    //                | primary ;
+    /// unary          → ( "!" | "-" ) unary
+    ///                | postfix ;
     fn unary(&mut self) -> Result<Expr, ParseError> {
-        let op_types = vec![TokenType::Bang, TokenType::Minus];
-        if self.token_type_match(&op_types) {
+        if self.token_type_match(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
             Ok(Expr::Unary(UnaryExpr {
@@ -238,15 +660,101 @@ impl Parser {
                 expression: Box::new(right),
             }))
         } else {
-            self.primary()
+            self.postfix()
+        }
+    }
+
+    /// postfix        → IDENTIFIER ( "++" | "--" )
+    ///                | call ;
+    fn postfix(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.call()?;
+        if self.token_type_match(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous().clone();
+            if let Expr::Variable(var_expr) = expr {
+                return Ok(Expr::Postfix(PostfixExpr {
+                    var: var_expr.var,
+                    operator,
+                    depth: Cell::new(None),
+                }));
+            }
+            return Err(ParseError::new(format!(
+                "[line {}] Operand of `{}` must be a variable",
+                operator.line, operator.lexeme
+            )));
+        }
+        Ok(expr)
+    }
+
+    /// call           → primary ( "(" arguments? ")" | "[" expression "]" )* ;
+    fn call(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.primary()?;
+        loop {
+            if self.match_one(TokenType::LeftParen) {
+                expr = self.finish_call(expr)?;
+            } else if self.match_one(TokenType::LeftBracket) {
+                self.open_delimiter();
+                let index = self.expression()?;
+                let bracket = self.close_delimiter(TokenType::RightBracket)?.clone();
+                expr = Expr::Index(IndexExpr {
+                    target: Box::new(expr),
+                    bracket,
+                    index: Box::new(index),
+                });
+            } else {
+                return Ok(expr);
+            }
         }
     }
 
-    // primary        → NUMBER | STRING | "true" | "false" | "nil"
-    //                | "(" expression ")" ;
-    //                | IDENTIFIER
+    fn finish_call(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        self.open_delimiter();
+        let arguments = self.arguments(TokenType::RightParen)?;
+        let paren = self.close_delimiter(TokenType::RightParen)?.clone();
+        Ok(Expr::Call(CallExpr {
+            callee: Box::new(callee),
+            paren,
+            arguments,
+        }))
+    }
+
+    /// arguments      → expression ( "," expression )* ;
+    fn arguments(&mut self, closer: TokenType) -> Result<Vec<Expr>, ParseError> {
+        let mut arguments = vec![];
+        if !self.check(closer) {
+            loop {
+                arguments.push(self.expression()?);
+                if !self.match_one(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        Ok(arguments)
+    }
+
+    /// entries        → expression ":" expression ( "," expression ":" expression )* ;
+    fn entries(&mut self) -> Result<Vec<(Expr, Expr)>, ParseError> {
+        let mut entries = vec![];
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key = self.expression()?;
+                self.consume(TokenType::Colon)?;
+                entries.push((key, self.expression()?));
+                if !self.match_one(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// primary        → NUMBER | STRING | "true" | "false" | "nil"
+    ///                | "(" expression ")"
+    ///                | IDENTIFIER
+    ///                | "func" "(" parameters? ")" block
+    ///                | "[" arguments? "]"
+    ///                | "{" entries? "}" ;
     fn primary(&mut self) -> Result<Expr, ParseError> {
-        let lit_types = vec![
+        let lit_types = [
             TokenType::False,
             TokenType::True,
             TokenType::Nil,
@@ -258,18 +766,42 @@ impl Parser {
             Ok(Expr::Literal(LiteralExpr {
                 token: self.previous().clone(),
             }))
-        } else if self.token_type_match(&vec![TokenType::LeftParen]) {
+        } else if self.match_one(TokenType::LeftParen) {
+            self.open_delimiter();
             let expr = self.expression()?;
-            self.consume(TokenType::RightParen).unwrap();
+            self.close_delimiter(TokenType::RightParen)?;
             Ok(Expr::Grouping(GroupingExpr {
                 expression: Box::new(expr),
             }))
-        } else if self.token_type_match(&vec![TokenType::Identifier]) {
+        } else if self.match_one(TokenType::Identifier) {
             Ok(Expr::Variable(VariableExpr {
                 var: self.previous().clone(),
+                depth: Cell::new(None),
             }))
+        } else if self.match_one(TokenType::Func) {
+            let line = self.previous().line;
+            let declaration = self.function_rest(None, line)?;
+            Ok(Expr::Function(FunctionExpr {
+                declaration: Rc::new(declaration),
+            }))
+        } else if self.match_one(TokenType::LeftBracket) {
+            let bracket = self.previous().clone();
+            self.open_delimiter();
+            let elements = self.arguments(TokenType::RightBracket)?;
+            self.close_delimiter(TokenType::RightBracket)?;
+            Ok(Expr::List(ListExpr { bracket, elements }))
+        } else if self.match_one(TokenType::LeftBrace) {
+            let brace = self.previous().clone();
+            self.open_delimiter();
+            let entries = self.entries()?;
+            self.close_delimiter(TokenType::RightBrace)?;
+            Ok(Expr::Map(MapExpr { brace, entries }))
         } else {
-            unreachable!()
+            let token = self.peek();
+            Err(ParseError::new(format!(
+                "[line {}] Expected an expression, but got `{}`",
+                token.line, token.lexeme
+            )))
         }
     }
 }
@@ -301,14 +833,47 @@ impl Parser {
         }
     }
 
-    fn token_type_match(&mut self, types: &Vec<TokenType>) -> bool {
-        for token_type in types {
-            if self.check(*token_type) {
-                self.advance();
-                return true;
-            }
+    /// whether the token after the current one is of `token_type`
+    fn check_next(&self, token_type: TokenType) -> bool {
+        self.tokens
+            .get(self.current + 1)
+            .is_some_and(|token| token.r#type == token_type)
+    }
+
+    /// consume the current token if it's of `token_type`
+    fn match_one(&mut self, token_type: TokenType) -> bool {
+        if self.check(token_type) {
+            self.advance();
+            true
+        } else {
+            false
         }
-        false
+    }
+
+    /// consume the current token if it's of any of `types`
+    /// callers pass array literals, so matching never allocates
+    fn token_type_match(&mut self, types: &[TokenType]) -> bool {
+        types.iter().any(|token_type| self.match_one(*token_type))
+    }
+
+    /// remember the just consumed `(`, `{` or `[` for the error of its missing closer
+    fn open_delimiter(&mut self) {
+        let opener = self.previous().clone();
+        self.open_delimiters.push(opener);
+    }
+
+    /// consume the closer of the innermost open delimiter
+    fn close_delimiter(&mut self, closer: TokenType) -> Result<&Token, ParseError> {
+        let opener = self
+            .open_delimiters
+            .pop()
+            .expect("every closer has an opener");
+        self.consume(closer).map_err(|err| {
+            ParseError::new(format!(
+                "{}; unmatched `{}` opened at line {}:{}",
+                err, opener.lexeme, opener.line, opener.column
+            ))
+        })
     }
 
     fn consume(&mut self, token_type: TokenType) -> Result<&Token, ParseError> {
@@ -317,8 +882,8 @@ impl Parser {
         } else {
             let cur = self.peek();
             Err(ParseError::new(format!(
-                "[line {}]Token type `{}` are expected, but got `{}`",
-                cur.line, token_type, cur.lexeme
+                "[line {}:{}] Token type `{}` are expected, but got `{}`",
+                cur.line, cur.column, token_type, cur.lexeme
             )))
         }
     }
@@ -326,6 +891,8 @@ impl Parser {
 
 impl Parser {
     fn synchronize(&mut self) {
+        // the delimiters of the failed statement are abandoned
+        self.open_delimiters.clear();
         self.advance();
 
         while !self.is_at_end() {
@@ -340,9 +907,13 @@ impl Parser {
                 | TokenType::Var
                 | TokenType::For
                 | TokenType::If
+                | TokenType::Unless
                 | TokenType::While
+                | TokenType::Until
                 | TokenType::Print
-                | TokenType::Return => {
+                | TokenType::Pprint
+                | TokenType::Return
+                | TokenType::Try => {
                     return;
                 }
                 _ => {}
@@ -352,3 +923,310 @@ impl Parser {
         }
     }
 }
+
+/// `!(condition)` for the sugar of `keyword`, reported at the keyword
+fn negate(keyword: &Token, condition: Expr) -> Expr {
+    Expr::Unary(UnaryExpr {
+        operator: Token::new(
+            TokenType::Bang,
+            String::from("!"),
+            keyword.line,
+            keyword.column,
+        ),
+        expression: Box::new(Expr::Grouping(GroupingExpr {
+            expression: Box::new(condition),
+        })),
+    })
+}
+
+#[test]
+fn test_parse_large_expression() {
+    let source_code = vec!["1"; 1_000].join(" + ");
+    let tokens = crate::scanner::Scanner::new(source_code)
+        .scan_tokens()
+        .unwrap();
+    let mut parser = Parser::new(tokens);
+    assert!(parser.parse_expression().is_ok());
+    assert!(parser.all_parsed());
+}
+
+#[test]
+fn test_modulo_precedence() {
+    let tokens = crate::scanner::Scanner::new("7 % 3 == 1 + 2 * 4 % 5".to_string())
+        .scan_tokens()
+        .unwrap();
+    let expr = Parser::new(tokens).parse_expression().unwrap();
+    assert_eq!(expr.to_string(), "(== (% 7 3) (+ 1 (% (* 2 4) 5)))");
+}
+
+#[test]
+fn test_ternary_associativity() {
+    let data = [
+        ("a ? b : c ? d : e", "(? a b (? c d e))"),
+        ("a or b ? c = 1 : d", "(? (or a b) c = 1 d)"),
+        ("x = a ? b : c", "x = (? a b c)"),
+    ];
+    for (src, should_be) in data {
+        let tokens = crate::scanner::Scanner::new(src.to_string())
+            .scan_tokens()
+            .unwrap();
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.parse_expression().unwrap().to_string(), should_be);
+        assert!(parser.all_parsed());
+    }
+
+    let tokens = crate::scanner::Scanner::new("a ? b;".to_string())
+        .scan_tokens()
+        .unwrap();
+    assert!(Parser::new(tokens).parse().is_err());
+}
+
+#[test]
+fn test_compound_assignment_desugar() {
+    let data = [
+        ("a += 1", "a = (+ a 1)"),
+        ("a -= b *= 2", "a = (- a b = (* b 2))"),
+        ("a /= 2 + 3", "a = (/ a (+ 2 3))"),
+    ];
+    for (src, should_be) in data {
+        let tokens = crate::scanner::Scanner::new(src.to_string())
+            .scan_tokens()
+            .unwrap();
+        let mut parser = Parser::new(tokens);
+        assert_eq!(parser.parse_expression().unwrap().to_string(), should_be);
+        assert!(parser.all_parsed());
+    }
+
+    let tokens = crate::scanner::Scanner::new("(a) += 1;\n1 *= 2;".to_string())
+        .scan_tokens()
+        .unwrap();
+    let errs = Parser::new(tokens).parse().err().unwrap();
+    let messages: Vec<String> = errs.iter().map(|err| err.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "[line 1:5] Invalid target `(grouping a)` for `+=`.",
+            "[line 2:3] Invalid target `1` for `*=`.",
+        ]
+    );
+}
+
+#[test]
+fn test_for_desugar() {
+    let data = vec![
+        (
+            "for (var i = 0; i < 3; i = i + 1) print i;",
+            "(block (var i = 0) (while (< i 3) (block (print i) i = (+ i 1))))",
+        ),
+        ("for (;;) {}", "(block (while true (block)))"),
+        (
+            "for (i = 0; ; i++) print i;",
+            "(block i = 0 (while true (block (print i) (post++ i))))",
+        ),
+    ];
+
+    for (input, should_be) in data {
+        let tokens = crate::scanner::Scanner::new(input.to_string())
+            .scan_tokens()
+            .unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        assert_eq!(stmts.len(), 1);
+        assert_eq!(stmts[0].to_string(), should_be);
+    }
+}
+
+#[test]
+fn test_unless_until_desugar() {
+    let data = vec![
+        ("unless (a) print 1;", "(if (! (grouping a)) (print 1))"),
+        (
+            "until (i == 3) i++;",
+            "(while (! (grouping (== i 3))) (post++ i))",
+        ),
+    ];
+
+    for (input, should_be) in data {
+        let tokens = crate::scanner::Scanner::new(input.to_string())
+            .scan_tokens()
+            .unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        assert_eq!(stmts.len(), 1);
+        assert_eq!(stmts[0].to_string(), should_be);
+    }
+}
+
+#[test]
+fn test_unexpected_token_in_expression() {
+    let tokens = crate::scanner::Scanner::new("* 3".to_string())
+        .scan_tokens()
+        .unwrap();
+    let result = Parser::new(tokens).parse_expression();
+    assert!(
+        matches!(result, Err(err) if err.to_string() == "[line 1] Expected an expression, but got `*`")
+    );
+
+    for input in [")", "print 1 + ;", "var a = +; print a;"] {
+        let tokens = crate::scanner::Scanner::new(input.to_string())
+            .scan_tokens()
+            .unwrap();
+        assert!(Parser::new(tokens).parse().is_err(), "{}", input);
+    }
+}
+
+#[test]
+fn test_unbalanced_grouping() {
+    let tokens = crate::scanner::Scanner::new("(1 + 2".to_string())
+        .scan_tokens()
+        .unwrap();
+    let result = Parser::new(tokens).parse_expression();
+    assert!(matches!(result, Err(err) if err.to_string().contains("Token type `)` are expected")));
+
+    let tokens = crate::scanner::Scanner::new("print (1 + 2; print 3;".to_string())
+        .scan_tokens()
+        .unwrap();
+    let errs = Parser::new(tokens).parse().err().unwrap();
+    assert_eq!(errs.len(), 1);
+    assert!(errs[0]
+        .to_string()
+        .contains("`)` are expected, but got `;`"));
+}
+
+#[test]
+fn test_assignment_in_condition() {
+    let data = vec![
+        "if (a = 1) print a;",
+        "while (a = 1) print a;",
+        "unless (a = 1) print a;",
+        "if (a += 1) print a;",
+    ];
+    for input in data {
+        let tokens = crate::scanner::Scanner::new(input.to_string())
+            .scan_tokens()
+            .unwrap();
+        let errs = Parser::new(tokens).parse().err().unwrap();
+        assert!(
+            errs[0].to_string().ends_with(
+                "Assignment to `a` in a condition, did you mean `==`? help: wrap it in parentheses if the assignment is intended"
+            ),
+            "{}",
+            input
+        );
+    }
+    let tokens = crate::scanner::Scanner::new("\nwhile (x = 1) {}".to_string())
+        .scan_tokens()
+        .unwrap();
+    let errs = Parser::new(tokens).parse().err().unwrap();
+    assert!(errs[0].to_string().starts_with("[line 2:8] Assignment"));
+
+    for input in ["if (a == 1) print a;", "while ((a = 1)) print a;"] {
+        let tokens = crate::scanner::Scanner::new(input.to_string())
+            .scan_tokens()
+            .unwrap();
+        assert!(Parser::new(tokens).parse().is_ok(), "{}", input);
+    }
+}
+
+#[test]
+fn test_try_stmt() {
+    let tokens =
+        crate::scanner::Scanner::new("try { a; } catch (e) { b; } finally { c; }".to_string())
+            .scan_tokens()
+            .unwrap();
+    let stmts = Parser::new(tokens).parse().unwrap();
+    assert_eq!(
+        stmts[0].to_string(),
+        "(try (block a) (catch e (block b)) (finally (block c)))"
+    );
+
+    let tokens = crate::scanner::Scanner::new("try { a; }\nprint 1;".to_string())
+        .scan_tokens()
+        .unwrap();
+    let errs = Parser::new(tokens).parse().err().unwrap();
+    assert_eq!(errs.len(), 1);
+    assert_eq!(
+        errs[0].to_string(),
+        "[line 1:1] Expected `catch` or `finally` after the `try` block"
+    );
+}
+
+#[test]
+fn test_unmatched_delimiters() {
+    let data = vec![
+        (
+            "print 1;\nprint (1 +\n2;",
+            "[line 3:2] Token type `)` are expected, but got `;`; unmatched `(` opened at line 2:7",
+        ),
+        (
+            "if (true) {\n  print 1;\n\n",
+            "[line 4:1] Token type `}` are expected, but got ``; unmatched `{` opened at line 1:11",
+        ),
+        (
+            "{\n  f(1,\n  (2));\n",
+            "[line 4:1] Token type `}` are expected, but got ``; unmatched `{` opened at line 1:1",
+        ),
+        (
+            "{\n  print f(1, (2);\n}",
+            "[line 2:17] Token type `)` are expected, but got `;`; unmatched `(` opened at line 2:10",
+        ),
+    ];
+    for (input, should_be) in data {
+        let tokens = crate::scanner::Scanner::new(input.to_string())
+            .scan_tokens()
+            .unwrap();
+        let errs = Parser::new(tokens).parse().err().unwrap();
+        assert_eq!(errs[0].to_string(), should_be, "{}", input);
+    }
+}
+
+#[test]
+fn test_from_range() {
+    let source_code = "var a = 1;\nprint a + 2;\nvar b = 3;";
+    let tokens = crate::scanner::Scanner::new(source_code.to_string())
+        .scan_tokens()
+        .unwrap();
+    // `print a + 2 ;`
+    let stmts = Parser::from_range(&tokens, 5, 10).parse().unwrap();
+    assert_eq!(stmts.len(), 1);
+    assert_eq!(stmts[0].to_string(), "(print (+ a 2))");
+
+    let stmts = Parser::from_range(&tokens, 5, tokens.len() - 1)
+        .parse()
+        .unwrap();
+    assert_eq!(stmts.len(), 2);
+
+    // cut before the `;`, the error points at the token after the range
+    let errs = Parser::from_range(&tokens, 5, 9).parse().err().unwrap();
+    assert_eq!(
+        errs[0].to_string(),
+        "[line 2:12] Token type `;` are expected, but got ``"
+    );
+}
+
+#[test]
+fn test_from_range_bounds() {
+    let tokens = crate::scanner::Scanner::new("print 1;".to_string())
+        .scan_tokens()
+        .unwrap();
+    // `end` past the tokens and `start` past `end` are clamped instead of panicking
+    let stmts = Parser::from_range(&tokens, 0, 100).parse().unwrap();
+    assert_eq!(stmts.len(), 1);
+    assert!(Parser::from_range(&tokens, 3, 1)
+        .parse()
+        .unwrap()
+        .is_empty());
+    assert!(Parser::from_range(&tokens, 100, 200)
+        .parse()
+        .unwrap()
+        .is_empty());
+    assert!(Parser::from_range(&[], 0, 0).parse().unwrap().is_empty());
+
+    // an Eof past the tokens takes the position of the last one
+    let errs = Parser::from_range(&tokens[..2], 0, 5)
+        .parse()
+        .err()
+        .unwrap();
+    assert_eq!(
+        errs[0].to_string(),
+        "[line 1:7] Token type `;` are expected, but got ``"
+    );
+}
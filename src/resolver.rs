@@ -0,0 +1,406 @@
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{
+    error::ParseError,
+    expression::Expr,
+    statement::{Block, FunctionStmt, Stmt},
+    token::Token,
+};
+
+/// a static pass between parsing and interpreting, it records on every variable use
+/// how many scopes out its declaration lives, mirroring the scopes the interpreter creates
+pub struct Resolver {
+    /// the local scopes, the innermost is last, the global scope is not tracked;
+    /// a name maps to whether its initializer has finished
+    scopes: Vec<HashMap<String, bool>>,
+    /// the names bound in the global scope, assigning any other global is an error
+    globals: HashSet<String>,
+    /// the globals already bound at the statement being resolved
+    defined_globals: HashSet<String>,
+    /// a global whose initializer is being resolved and that has no earlier binding
+    uninitialized_global: Option<String>,
+    errors: Vec<ParseError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![],
+            globals: HashSet::new(),
+            defined_globals: HashSet::new(),
+            uninitialized_global: None,
+            errors: vec![],
+        }
+    }
+
+    /// globals defined before the program runs, like the natives or earlier REPL lines
+    pub fn globals(mut self, names: impl IntoIterator<Item = String>) -> Self {
+        for name in names {
+            self.globals.insert(name.clone());
+            self.defined_globals.insert(name);
+        }
+        self
+    }
+
+    /// resolve a whole program, collecting every error
+    pub fn resolve(mut self, stmts: &[Stmt]) -> Result<(), Vec<ParseError>> {
+        self.declare_globals(stmts);
+        self.resolve_stmts(stmts);
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// resolve each top-level statement on its own, the errors of one leave the others
+    /// usable, e.g. for the REPL to still run the good statements of a line
+    pub fn resolve_each(mut self, stmts: &[Stmt]) -> Vec<Result<(), Vec<ParseError>>> {
+        self.declare_globals(stmts);
+        stmts
+            .iter()
+            .map(|stmt| {
+                self.resolve_stmt(stmt);
+                let errors = std::mem::take(&mut self.errors);
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(errors)
+                }
+            })
+            .collect()
+    }
+
+    /// a function may assign a global declared after it
+    fn declare_globals(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            let name = match stmt {
+                Stmt::Var(var_stmt) => Some(&var_stmt.var_name),
+                Stmt::Function(declaration) => declaration.name.as_ref(),
+                Stmt::Capture(capture) => Some(&capture.var_name),
+                _ => None,
+            };
+            self.globals.extend(name.cloned());
+        }
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Var(var_stmt) => {
+                let name = &var_stmt.var_name;
+                self.declare(name);
+                // a redeclared global may still read its previous binding
+                if self.scopes.is_empty() && !self.defined_globals.contains(name) {
+                    self.uninitialized_global = Some(name.clone());
+                }
+                if let Some(initializer) = &var_stmt.initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.uninitialized_global = None;
+                self.define(name);
+            }
+            Stmt::Print(stmt) => self.resolve_expr(&stmt.expr),
+            Stmt::Expr(stmt) => self.resolve_expr(&stmt.expr),
+            Stmt::Block(block) => self.resolve_block(block),
+            Stmt::Capture(capture) => {
+                self.resolve_block(&capture.body);
+                // bound after the block, in the enclosing scope
+                self.define(&capture.var_name);
+            }
+            Stmt::If(if_stmt) => {
+                self.resolve_expr(&if_stmt.condition);
+                self.resolve_stmt(&if_stmt.then_branch);
+                if let Some(else_branch) = &if_stmt.else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                self.resolve_expr(&while_stmt.condition);
+                self.resolve_stmt(&while_stmt.body);
+            }
+            Stmt::Function(declaration) => {
+                // defined before the body so it can call itself
+                if let Some(name) = &declaration.name {
+                    self.define(name);
+                }
+                self.resolve_function(declaration);
+            }
+            Stmt::Return(return_stmt) => {
+                if let Some(value) = &return_stmt.value {
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Try(try_stmt) => {
+                self.resolve_block(&try_stmt.body);
+                if let Some(catch) = &try_stmt.catch {
+                    // the error is bound in a scope of its own around the block
+                    self.scopes.push(HashMap::new());
+                    self.define(&catch.var_name);
+                    self.resolve_block(&catch.body);
+                    self.scopes.pop();
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    self.resolve_block(finally);
+                }
+            }
+        }
+    }
+
+    /// the parameters and the body share one scope, like the call does
+    fn resolve_function(&mut self, declaration: &FunctionStmt) {
+        self.scopes.push(HashMap::new());
+        for param in &declaration.params {
+            self.define(param);
+        }
+        self.resolve_stmts(&declaration.body.stmts);
+        self.scopes.pop();
+    }
+
+    fn resolve_block(&mut self, block: &Block) {
+        self.scopes.push(HashMap::new());
+        self.resolve_stmts(&block.stmts);
+        self.scopes.pop();
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Binary(binary) => {
+                self.resolve_expr(&binary.left);
+                self.resolve_expr(&binary.right);
+            }
+            Expr::Logical(logical) => {
+                self.resolve_expr(&logical.left);
+                self.resolve_expr(&logical.right);
+            }
+            Expr::Unary(unary) => self.resolve_expr(&unary.expression),
+            Expr::Grouping(grouping) => self.resolve_expr(&grouping.expression),
+            Expr::Literal(_) => {}
+            Expr::Variable(var) => {
+                let in_initializer = self
+                    .scopes
+                    .last()
+                    .and_then(|scope| scope.get(&var.var.lexeme))
+                    == Some(&false);
+                if in_initializer {
+                    self.errors.push(ParseError::new(format!(
+                        "[line {}] Can't read local variable '{}' in its own initializer.",
+                        var.var.line, var.var.lexeme
+                    )));
+                }
+                // a function body in the initializer runs later, it has a scope of its own
+                let in_global_initializer = self.scopes.is_empty()
+                    && self.uninitialized_global.as_ref() == Some(&var.var.lexeme);
+                if in_global_initializer {
+                    self.errors.push(ParseError::new(format!(
+                        "[line {}] Can't read global variable '{}' in its own initializer.",
+                        var.var.line, var.var.lexeme
+                    )));
+                }
+                self.resolve_local(&var.depth, &var.var);
+            }
+            Expr::Assign(assign) => {
+                self.resolve_expr(&assign.value);
+                self.resolve_local(&assign.depth, &assign.lvar);
+                let name = &assign.lvar.lexeme;
+                let is_local = self.scopes.iter().any(|scope| scope.contains_key(name));
+                if !is_local && !self.globals.contains(name) {
+                    self.errors.push(ParseError::new(format!(
+                        "[line {}] Undefined variable `{}`. help: declare it first with `var {} = ...;`",
+                        assign.lvar.line, name, name
+                    )));
+                }
+            }
+            Expr::Postfix(postfix) => self.resolve_local(&postfix.depth, &postfix.var),
+            Expr::Call(call) => {
+                self.resolve_expr(&call.callee);
+                for argument in &call.arguments {
+                    self.resolve_expr(argument);
+                }
+            }
+            Expr::Ternary(ternary) => {
+                self.resolve_expr(&ternary.condition);
+                self.resolve_expr(&ternary.then_branch);
+                self.resolve_expr(&ternary.else_branch);
+            }
+            Expr::Function(function) => self.resolve_function(&function.declaration),
+            Expr::List(list) => {
+                for element in &list.elements {
+                    self.resolve_expr(element);
+                }
+            }
+            Expr::Map(map) => {
+                for (key, value) in &map.entries {
+                    self.resolve_expr(key);
+                    self.resolve_expr(value);
+                }
+            }
+            Expr::Index(index) => {
+                self.resolve_expr(&index.target);
+                self.resolve_expr(&index.index);
+            }
+        }
+    }
+
+    /// a name not declared in any local scope is a global, all the scopes out
+    fn resolve_local(&mut self, depth: &Cell<Option<usize>>, name: &Token) {
+        let hops = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme))
+            .unwrap_or(self.scopes.len());
+        depth.set(Some(hops));
+    }
+
+    /// a local whose initializer is not resolved yet, globals are not tracked
+    fn declare(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), false);
+        }
+    }
+
+    fn define(&mut self, name: &str) {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                scope.insert(name.to_string(), true);
+            }
+            None => {
+                self.defined_globals.insert(name.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn resolve_source(source: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    let tokens = crate::scanner::Scanner::new(source.to_string())
+        .scan_tokens()
+        .unwrap();
+    let stmts = crate::parser::Parser::new(tokens).parse()?;
+    Resolver::new().resolve(&stmts)?;
+    Ok(stmts)
+}
+
+#[test]
+fn test_nested_block_resolution() {
+    let stmts = resolve_source("var a = 1; { var b = 2; { print a; print b; b = 3; } }").unwrap();
+    let Stmt::Block(outer) = &stmts[1] else {
+        panic!("expected a block")
+    };
+    let Stmt::Block(inner) = &outer.stmts[1] else {
+        panic!("expected a block")
+    };
+    let depths: Vec<Option<usize>> = inner
+        .stmts
+        .iter()
+        .map(|stmt| match stmt {
+            Stmt::Print(print) => match &print.expr {
+                Expr::Variable(var) => var.depth.get(),
+                _ => None,
+            },
+            Stmt::Expr(stmt) => match &stmt.expr {
+                Expr::Assign(assign) => assign.depth.get(),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    // `a` is the global two scopes out, `b` is one scope out
+    assert_eq!(depths, [Some(2), Some(1), Some(1)]);
+}
+
+#[test]
+fn test_self_reference_in_initializer() {
+    let errs = resolve_source("{ var a = a; }\n{ var b = 1 + b; }")
+        .err()
+        .unwrap();
+    let messages: Vec<String> = errs.iter().map(|err| err.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "[line 1] Can't read local variable 'a' in its own initializer.",
+            "[line 2] Can't read local variable 'b' in its own initializer.",
+        ]
+    );
+
+    // a global may still read the previous binding of the same name
+    assert!(resolve_source("var a = 1; var a = a + 1;").is_ok());
+    // but not itself when there is none
+    let errs = resolve_source("var a = a;\nvar b = -b;").err().unwrap();
+    let messages: Vec<String> = errs.iter().map(|err| err.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "[line 1] Can't read global variable 'a' in its own initializer.",
+            "[line 2] Can't read global variable 'b' in its own initializer.",
+        ]
+    );
+    // a function in the initializer reads the global once it is defined
+    assert!(resolve_source("var f = func () { return f; };").is_ok());
+    // an outer local may be read from a nested scope
+    assert!(resolve_source("{ var a = 1; { var b = a; } }").is_ok());
+}
+
+#[test]
+fn test_assign_undeclared() {
+    let errs = resolve_source("a = 1;\n{ b += 2; }\nfunc f() { c = 3; }")
+        .err()
+        .unwrap();
+    let messages: Vec<String> = errs.iter().map(|err| err.to_string()).collect();
+    assert_eq!(
+        messages,
+        [
+            "[line 1] Undefined variable `a`. help: declare it first with `var a = ...;`",
+            "[line 2] Undefined variable `b`. help: declare it first with `var b = ...;`",
+            "[line 3] Undefined variable `c`. help: declare it first with `var c = ...;`",
+        ]
+    );
+
+    // a global declared anywhere at the top level, even after the assignment
+    assert!(resolve_source("func f() { a = 1; } var a; f();").is_ok());
+    assert!(resolve_source("capture { print 1; } into s; s = 1;").is_ok());
+    assert!(resolve_source("func f(a) { a = 1; { var b; b = a; } }").is_ok());
+    // or one known to be defined before the program
+    let tokens = crate::scanner::Scanner::new("a = 1;".to_string())
+        .scan_tokens()
+        .unwrap();
+    let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+    assert!(Resolver::new()
+        .globals(["a".to_string()])
+        .resolve(&stmts)
+        .is_ok());
+}
+
+#[test]
+fn test_resolve_each() {
+    let tokens =
+        crate::scanner::Scanner::new("print 1;\nb = 1;\nfunc f() { c = 1; } var c;".to_string())
+            .scan_tokens()
+            .unwrap();
+    let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+    let results = Resolver::new().resolve_each(&stmts);
+    assert_eq!(results.len(), 4);
+    assert!(results[0].is_ok() && results[2].is_ok() && results[3].is_ok());
+    let errs = results[1].as_ref().unwrap_err();
+    assert_eq!(
+        errs[0].to_string(),
+        "[line 2] Undefined variable `b`. help: declare it first with `var b = ...;`"
+    );
+}
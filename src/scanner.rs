@@ -1,165 +1,387 @@
-use std::{collections::HashMap, vec};
+use std::{collections::HashMap, sync::OnceLock};
 
-use crate::token::{Token, TokenType};
+use crate::{
+    error::ScanError,
+    token::{Token, TokenType},
+};
 pub struct Scanner {
-    source_code: String,
+    /// indexed by `start` and `current`, so looking a character up is O(1)
+    source: Vec<char>,
     start: usize,
     current: usize,
     line: usize,
+    /// index of the first character of the current line
+    line_start: usize,
+    /// 1-based column of the token being scanned
+    column: usize,
+    /// columns between tab stops, a tab moves the column to the next stop
+    tab_width: usize,
+    /// a source index on the current line and its column, so columns are
+    /// counted from here instead of from the line start every token
+    column_cursor: (usize, usize),
 }
 
+/// the tab width of a new `Scanner`
+pub const DEFAULT_TAB_WIDTH: usize = 1;
+
 impl Scanner {
     pub fn new(source_code: String) -> Self {
         Self {
-            source_code,
+            source: source_code.chars().collect(),
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            column: 1,
+            tab_width: DEFAULT_TAB_WIDTH,
+            column_cursor: (0, 1),
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
+    /// count tabs as `width` columns to the next tab stop, at least 1
+    pub fn tab_width(mut self, width: usize) -> Self {
+        self.tab_width = width.max(1);
+        self
+    }
+
+    /// scan the whole source, or every invalid token if there is any
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<ScanError>> {
         let mut tokens = vec![];
+        let mut errors = vec![];
         while let Some(token) = self.scan_token() {
             match token.r#type {
-                TokenType::Invalid => {
-                    println!("[line {}]invalid token: {}", token.line, token.lexeme)
-                }
+                TokenType::Invalid => errors.push(ScanError::new(token.lexeme, token.line)),
                 TokenType::Blank => {}
                 _ => tokens.push(token),
             }
         }
-        tokens.push(Token::new(TokenType::Eof, String::new(), self.line));
-        tokens
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let column = self.column_of(self.current);
+        tokens.push(Token::new(TokenType::Eof, String::new(), self.line, column));
+        Ok(tokens)
+    }
+
+    /// 1-based column of the source index `index` on the current line,
+    /// indexes are asked for in increasing order as the scan moves forward
+    fn column_of(&mut self, index: usize) -> usize {
+        let (mut at, mut column) = self.column_cursor;
+        if at < self.line_start {
+            (at, column) = (self.line_start, 1);
+        }
+        for ch in &self.source[at..index] {
+            column = match ch {
+                '\t' => (column - 1) / self.tab_width * self.tab_width + self.tab_width + 1,
+                _ => column + 1,
+            };
+        }
+        self.column_cursor = (index, column);
+        column
+    }
+
+    fn char_at(&self, index: usize) -> Option<char> {
+        self.source.get(index).copied()
     }
 
     fn scan_token(&mut self) -> Option<Token> {
         self.start = self.current;
-        self.source_code
-            .chars()
-            .nth(self.current)
-            .map(|ch| match ch {
-                '(' => {
-                    self.current += 1;
-                    Token::new(TokenType::LeftParen, String::from("("), self.line)
-                }
-                '{' => {
-                    self.current += 1;
-                    Token::new(TokenType::LeftBrace, String::from("{"), self.line)
-                }
-                '}' => {
-                    self.current += 1;
-                    Token::new(TokenType::RightBrace, String::from("}"), self.line)
-                }
-                ')' => {
-                    self.current += 1;
-                    Token::new(TokenType::RightParen, String::from(")"), self.line)
-                }
-                ',' => {
-                    self.current += 1;
-                    Token::new(TokenType::Comma, String::from(","), self.line)
-                }
-                '.' => {
-                    self.current += 1;
-                    Token::new(TokenType::Dot, String::from("."), self.line)
-                }
-                '-' => {
-                    self.current += 1;
-                    Token::new(TokenType::Minus, String::from("-"), self.line)
-                }
-                '+' => {
+        self.column = self.column_of(self.start);
+        self.char_at(self.current).map(|ch| match ch {
+            '(' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::LeftParen,
+                    String::from("("),
+                    self.line,
+                    self.column,
+                )
+            }
+            '{' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::LeftBrace,
+                    String::from("{"),
+                    self.line,
+                    self.column,
+                )
+            }
+            '}' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::RightBrace,
+                    String::from("}"),
+                    self.line,
+                    self.column,
+                )
+            }
+            ')' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::RightParen,
+                    String::from(")"),
+                    self.line,
+                    self.column,
+                )
+            }
+            '[' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::LeftBracket,
+                    String::from("["),
+                    self.line,
+                    self.column,
+                )
+            }
+            ']' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::RightBracket,
+                    String::from("]"),
+                    self.line,
+                    self.column,
+                )
+            }
+            ',' => {
+                self.current += 1;
+                Token::new(TokenType::Comma, String::from(","), self.line, self.column)
+            }
+            ':' => {
+                self.current += 1;
+                Token::new(TokenType::Colon, String::from(":"), self.line, self.column)
+            }
+            '?' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::Question,
+                    String::from("?"),
+                    self.line,
+                    self.column,
+                )
+            }
+            '.' => {
+                self.current += 1;
+                Token::new(TokenType::Dot, String::from("."), self.line, self.column)
+            }
+            '-' => {
+                self.current += 1;
+                if self.char_at(self.current) == Some('-') {
                     self.current += 1;
-                    Token::new(TokenType::Plus, String::from("+"), self.line)
-                }
-                ';' => {
+                    Token::new(
+                        TokenType::MinusMinus,
+                        String::from("--"),
+                        self.line,
+                        self.column,
+                    )
+                } else if self.char_at(self.current) == Some('=') {
                     self.current += 1;
-                    Token::new(TokenType::Semicolon, String::from(";"), self.line)
+                    Token::new(
+                        TokenType::MinusEqual,
+                        String::from("-="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
+                    Token::new(TokenType::Minus, String::from("-"), self.line, self.column)
                 }
-                '*' => {
+            }
+            '+' => {
+                self.current += 1;
+                if self.char_at(self.current) == Some('+') {
                     self.current += 1;
-                    Token::new(TokenType::Star, String::from("*"), self.line)
-                }
-                '/' => {
+                    Token::new(
+                        TokenType::PlusPlus,
+                        String::from("++"),
+                        self.line,
+                        self.column,
+                    )
+                } else if self.char_at(self.current) == Some('=') {
                     self.current += 1;
-                    Token::new(TokenType::Slash, String::from("/"), self.line)
+                    Token::new(
+                        TokenType::PlusEqual,
+                        String::from("+="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
+                    Token::new(TokenType::Plus, String::from("+"), self.line, self.column)
                 }
-                '!' => {
+            }
+            ';' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::Semicolon,
+                    String::from(";"),
+                    self.line,
+                    self.column,
+                )
+            }
+            '*' => {
+                self.current += 1;
+                if self.char_at(self.current) == Some('=') {
                     self.current += 1;
-                    if self.source_code.chars().nth(self.current) == Some('=') {
-                        self.current += 1;
-                        Token::new(TokenType::BangEqual, String::from("!="), self.line)
-                    } else {
-                        Token::new(TokenType::Bang, String::from("!"), self.line)
-                    }
+                    Token::new(
+                        TokenType::StarEqual,
+                        String::from("*="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
+                    Token::new(TokenType::Star, String::from("*"), self.line, self.column)
                 }
-                '=' => {
+            }
+            '%' => {
+                self.current += 1;
+                Token::new(
+                    TokenType::Percent,
+                    String::from("%"),
+                    self.line,
+                    self.column,
+                )
+            }
+            '/' => {
+                let next = self.char_at(self.current + 1);
+                if next == Some('*') {
+                    self.block_comment()
+                } else if next == Some('/') {
+                    self.line_comment()
+                } else if next == Some('=') {
+                    self.current += 2;
+                    Token::new(
+                        TokenType::SlashEqual,
+                        String::from("/="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
                     self.current += 1;
-                    if self.source_code.chars().nth(self.current) == Some('=') {
-                        self.current += 1;
-                        Token::new(TokenType::EqualEqual, String::from("=="), self.line)
-                    } else {
-                        Token::new(TokenType::Equal, String::from("="), self.line)
-                    }
+                    Token::new(TokenType::Slash, String::from("/"), self.line, self.column)
                 }
-                '>' => {
+            }
+            '!' => {
+                self.current += 1;
+                if self.char_at(self.current) == Some('=') {
                     self.current += 1;
-                    if self.source_code.chars().nth(self.current) == Some('=') {
-                        self.current += 1;
-                        Token::new(TokenType::GreaterEqual, String::from(">="), self.line)
-                    } else {
-                        Token::new(TokenType::Greater, String::from(">"), self.line)
-                    }
+                    Token::new(
+                        TokenType::BangEqual,
+                        String::from("!="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
+                    Token::new(TokenType::Bang, String::from("!"), self.line, self.column)
                 }
-                '<' => {
+            }
+            '=' => {
+                self.current += 1;
+                if self.char_at(self.current) == Some('=') {
                     self.current += 1;
-                    if self.source_code.chars().nth(self.current) == Some('=') {
-                        self.current += 1;
-                        Token::new(TokenType::LessEqual, String::from("<="), self.line)
-                    } else {
-                        Token::new(TokenType::Less, String::from("<"), self.line)
-                    }
+                    Token::new(
+                        TokenType::EqualEqual,
+                        String::from("=="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
+                    Token::new(TokenType::Equal, String::from("="), self.line, self.column)
                 }
-                '\n' => {
-                    let token = Token::new(TokenType::Blank, String::from(ch), self.line);
+            }
+            '>' => {
+                self.current += 1;
+                if self.char_at(self.current) == Some('=') {
                     self.current += 1;
-                    self.line += 1;
-                    token
+                    Token::new(
+                        TokenType::GreaterEqual,
+                        String::from(">="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
+                    Token::new(
+                        TokenType::Greater,
+                        String::from(">"),
+                        self.line,
+                        self.column,
+                    )
                 }
-                ' ' | '\t' | '\r' => {
+            }
+            '<' => {
+                self.current += 1;
+                if self.char_at(self.current) == Some('=') {
                     self.current += 1;
-                    Token::new(TokenType::Blank, String::from(ch), self.line)
+                    Token::new(
+                        TokenType::LessEqual,
+                        String::from("<="),
+                        self.line,
+                        self.column,
+                    )
+                } else {
+                    Token::new(TokenType::Less, String::from("<"), self.line, self.column)
                 }
-                'A'..='Z' | 'a'..='z' => self.identifier(),
-                '0'..='9' => self.number(),
-                '"' => self.string(),
-                invalid => {
+            }
+            // `&&` and `||` are aliases of `and` and `or`
+            '&' | '|' => {
+                self.current += 1;
+                let keyword = if ch == '&' { "and" } else { "or" };
+                if self.char_at(self.current) == Some(ch) {
                     self.current += 1;
-                    Token::new(TokenType::Invalid, invalid.into(), self.line)
+                    let token_type = keyword_token(keyword).unwrap();
+                    Token::new(token_type, format!("{}{}", ch, ch), self.line, self.column)
+                } else {
+                    Token::new(
+                        TokenType::Invalid,
+                        format!(
+                            "{} (help: bitwise operators are not supported, use `{}{}` or `{}`)",
+                            ch, ch, ch, keyword
+                        ),
+                        self.line,
+                        self.column,
+                    )
                 }
-            })
+            }
+            '\n' => {
+                let token = Token::new(TokenType::Blank, String::from(ch), self.line, self.column);
+                self.current += 1;
+                self.line += 1;
+                self.line_start = self.current;
+                token
+            }
+            ' ' | '\t' | '\r' => {
+                self.current += 1;
+                Token::new(TokenType::Blank, String::from(ch), self.line, self.column)
+            }
+            'A'..='Z' | 'a'..='z' | '_' => self.identifier(),
+            '0'..='9' => self.number(),
+            '"' | '\'' => self.string(),
+            invalid => {
+                self.current += 1;
+                Token::new(TokenType::Invalid, invalid.into(), self.line, self.column)
+            }
+        })
     }
 
     fn identifier(&mut self) -> Token {
         let mut token = String::new();
-        while let Some(ch) = self.source_code.chars().nth(self.current) {
-            if ch.is_alphanumeric() {
+        while let Some(ch) = self.char_at(self.current) {
+            if ch.is_alphanumeric() || ch == '_' {
                 token.push(ch);
                 self.current += 1;
             } else {
                 break;
             }
         }
-        if let Some(preserved) = preserved_word(token.as_str(), self.line) {
-            preserved
-        } else {
-            Token::new(TokenType::Identifier, token, self.line)
-        }
+        let token_type = keyword_token(&token).unwrap_or(TokenType::Identifier);
+        Token::new(token_type, token, self.line, self.column)
     }
 
     fn number(&mut self) -> Token {
+        let next = self.char_at(self.current + 1);
+        if self.char_at(self.current) == Some('0') && matches!(next, Some('x') | Some('X')) {
+            return self.hex_number();
+        }
         let mut token = String::new();
         let mut dot_consumed = false;
-        while let Some(ch) = self.source_code.chars().nth(self.current) {
+        while let Some(ch) = self.char_at(self.current) {
             if ch.is_numeric() {
                 token.push(ch);
                 self.current += 1;
@@ -172,86 +394,531 @@ impl Scanner {
             }
         }
         // error number parse handle
-        Token::new(TokenType::Number, token, self.line)
+        Token::new(TokenType::Number, token, self.line, self.column)
+    }
+
+    /// skip a `//` comment up to the newline, which is left for the line count
+    fn line_comment(&mut self) -> Token {
+        while let Some(ch) = self.char_at(self.current) {
+            if ch == '\n' {
+                break;
+            }
+            self.current += 1;
+        }
+        Token::new(TokenType::Blank, String::new(), self.line, self.column)
+    }
+
+    /// skip a /* ... */ comment, which may be nested
+    fn block_comment(&mut self) -> Token {
+        let start_line = self.line;
+        // skip the opening /*
+        self.current += 2;
+        let mut depth = 1;
+        while let Some(ch) = self.char_at(self.current) {
+            let next = self.char_at(self.current + 1);
+            match (ch, next) {
+                ('/', Some('*')) => {
+                    depth += 1;
+                    self.current += 2;
+                }
+                ('*', Some('/')) => {
+                    depth -= 1;
+                    self.current += 2;
+                    if depth == 0 {
+                        return Token::new(
+                            TokenType::Blank,
+                            String::new(),
+                            start_line,
+                            self.column,
+                        );
+                    }
+                }
+                _ => {
+                    if ch == '\n' {
+                        self.line += 1;
+                        self.line_start = self.current + 1;
+                    }
+                    self.current += 1;
+                }
+            }
+        }
+        Token::new(
+            TokenType::Invalid,
+            format!("unterminated block comment starting at line {}", start_line),
+            self.line,
+            self.column,
+        )
+    }
+
+    /// C99-style hex float like 0x1.8p3, the `p` exponent may only be
+    /// omitted when there is no fraction (0xff)
+    fn hex_number(&mut self) -> Token {
+        let mut token = String::new();
+        while let Some(ch) = self.char_at(self.current) {
+            let after_exponent = matches!(token.chars().last(), Some('p') | Some('P'));
+            if ch.is_alphanumeric() || ch == '.' || (after_exponent && (ch == '+' || ch == '-')) {
+                token.push(ch);
+                self.current += 1;
+            } else {
+                break;
+            }
+        }
+        if parse_hex_float(&token).is_some() {
+            Token::new(TokenType::Number, token, self.line, self.column)
+        } else {
+            Token::new(TokenType::Invalid, token, self.line, self.column)
+        }
     }
 
-    /// expect to parse a string literal like "aaa"
+    /// expect to parse a string literal like "aaa" or 'aaa'
+    /// the closing quote must match the opening one
     /// do not support \
     fn string(&mut self) -> Token {
         let mut token = String::new();
+        let quote = self.char_at(self.current).unwrap();
+        let start_line = self.line;
         // skip the first quote
         self.current += 1;
-        while let Some(ch) = self.source_code.chars().nth(self.current) {
-            if ch == '\n' {
-                self.line += 1;
-            } else if ch != '"' {
-                token.push(ch);
-            }
+        let mut terminated = false;
+        while let Some(ch) = self.char_at(self.current) {
             self.current += 1;
-
             // out of this loop when meeting the second quote
-            if ch == '"' {
+            if ch == quote {
+                terminated = true;
                 break;
             }
+            if ch == '\n' {
+                self.line += 1;
+                self.line_start = self.current;
+            } else {
+                token.push(ch);
+            }
+        }
+        if terminated {
+            Token::new(TokenType::String, token, self.line, self.column)
+        } else {
+            Token::new(
+                TokenType::Invalid,
+                format!("Unterminated string starting at line {}", start_line),
+                start_line,
+                self.column,
+            )
         }
-        Token::new(TokenType::String, token, self.line)
     }
 }
 
-fn preserved_word(token: &str, line: usize) -> Option<Token> {
-    match token {
-        "and" => Some(Token::new(TokenType::And, "and".to_string(), line)),
-        "class" => Some(Token::new(TokenType::Class, "class".to_string(), line)),
-        "else" => Some(Token::new(TokenType::Else, "else".to_string(), line)),
-        "false" => Some(Token::new(TokenType::False, "false".to_string(), line)),
-        "for" => Some(Token::new(TokenType::For, "for".to_string(), line)),
-        "func" => Some(Token::new(TokenType::Func, "func".to_string(), line)),
-        "if" => Some(Token::new(TokenType::If, "if".to_string(), line)),
-        "nil" => Some(Token::new(TokenType::Nil, "nil".to_string(), line)),
-        "or" => Some(Token::new(TokenType::Or, "or".to_string(), line)),
-        "print" => Some(Token::new(TokenType::Print, "print".to_string(), line)),
-        "return" => Some(Token::new(TokenType::Return, "return".to_string(), line)),
-        "super" => Some(Token::new(TokenType::Super, "super".to_string(), line)),
-        "this" => Some(Token::new(TokenType::This, "this".to_string(), line)),
-        "true" => Some(Token::new(TokenType::True, "true".to_string(), line)),
-        "var" => Some(Token::new(TokenType::Var, "var".to_string(), line)),
-        "while" => Some(Token::new(TokenType::While, "while".to_string(), line)),
-        _ => None,
+/// value of a hex number literal like 0x1.8p3, `None` if malformed
+pub fn parse_hex_float(literal: &str) -> Option<f64> {
+    let body = literal
+        .strip_prefix("0x")
+        .or_else(|| literal.strip_prefix("0X"))?;
+    let (mantissa, exponent) = match body.find(['p', 'P']) {
+        Some(i) => (&body[..i], Some(&body[i + 1..])),
+        None => (body, None),
+    };
+    let (int_part, frac_part) = match mantissa.split_once('.') {
+        Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+        None => (mantissa, None),
+    };
+    // a fraction needs the binary exponent, and there must be some digits
+    if (frac_part.is_some() && exponent.is_none())
+        || int_part.len() + frac_part.map_or(0, str::len) == 0
+    {
+        return None;
+    }
+
+    let mut value = 0f64;
+    for ch in int_part.chars() {
+        value = value * 16.0 + ch.to_digit(16)? as f64;
+    }
+    let mut scale = 1.0 / 16.0;
+    for ch in frac_part.unwrap_or("").chars() {
+        value += ch.to_digit(16)? as f64 * scale;
+        scale /= 16.0;
     }
+    if let Some(exponent) = exponent {
+        let digits = exponent.trim_start_matches(['+', '-']);
+        if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_digit()) {
+            return None;
+        }
+        value *= 2f64.powi(exponent.parse::<i32>().ok()?);
+    }
+    Some(value)
+}
+
+/// every reserved word with the token type it scans to
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("and", TokenType::And),
+    ("capture", TokenType::Capture),
+    ("catch", TokenType::Catch),
+    ("class", TokenType::Class),
+    ("elif", TokenType::Elif),
+    ("else", TokenType::Else),
+    ("false", TokenType::False),
+    ("finally", TokenType::Finally),
+    ("for", TokenType::For),
+    ("func", TokenType::Func),
+    ("if", TokenType::If),
+    ("into", TokenType::Into),
+    ("nil", TokenType::Nil),
+    ("or", TokenType::Or),
+    ("pprint", TokenType::Pprint),
+    ("print", TokenType::Print),
+    ("return", TokenType::Return),
+    ("super", TokenType::Super),
+    ("this", TokenType::This),
+    ("true", TokenType::True),
+    ("try", TokenType::Try),
+    ("unless", TokenType::Unless),
+    ("until", TokenType::Until),
+    ("var", TokenType::Var),
+    ("while", TokenType::While),
+];
+
+/// whether `s` is a reserved word of the language
+pub fn is_keyword(s: &str) -> bool {
+    keyword_token(s).is_some()
+}
+
+/// the token type of the reserved word `s`, `None` for anything else
+pub fn keyword_token(s: &str) -> Option<TokenType> {
+    static KEYWORD_MAP: OnceLock<HashMap<&str, TokenType>> = OnceLock::new();
+    KEYWORD_MAP
+        .get_or_init(|| KEYWORDS.iter().copied().collect())
+        .get(s)
+        .copied()
 }
 
 #[test]
 fn test_run() {
     let source_code = "var id = 114.514;";
 
-    let tokens = Scanner::new(source_code.to_string()).scan_tokens();
+    let tokens = Scanner::new(source_code.to_string()).scan_tokens().unwrap();
+    let should_be = vec![
+        Token::new(TokenType::Var, "var".to_string(), 1, 1),
+        Token::new(TokenType::Identifier, "id".to_string(), 1, 5),
+        Token::new(TokenType::Equal, "=".to_string(), 1, 8),
+        Token::new(TokenType::Number, "114.514".to_string(), 1, 10),
+        Token::new(TokenType::Semicolon, ";".to_string(), 1, 17),
+        Token::new(TokenType::Eof, String::new(), 1, 18),
+    ];
+    assert_eq!(tokens, should_be);
+
+    let source_code = "while (a == 114) {\n var b = \"while\";\n }\n";
+    let tokens = Scanner::new(source_code.to_string()).scan_tokens().unwrap();
+    let should_be = vec![
+        Token::new(TokenType::While, "while".to_string(), 1, 1),
+        Token::new(TokenType::LeftParen, "(".to_string(), 1, 7),
+        Token::new(TokenType::Identifier, "a".to_string(), 1, 8),
+        Token::new(TokenType::EqualEqual, "==".to_string(), 1, 10),
+        Token::new(TokenType::Number, "114".to_string(), 1, 13),
+        Token::new(TokenType::RightParen, ")".to_string(), 1, 16),
+        Token::new(TokenType::LeftBrace, "{".to_string(), 1, 18),
+        Token::new(TokenType::Var, "var".to_string(), 2, 2),
+        Token::new(TokenType::Identifier, "b".to_string(), 2, 6),
+        Token::new(TokenType::Equal, "=".to_string(), 2, 8),
+        Token::new(TokenType::String, "while".to_string(), 2, 10),
+        Token::new(TokenType::Semicolon, ";".to_string(), 2, 17),
+        Token::new(TokenType::RightBrace, "}".to_string(), 3, 2),
+        Token::new(TokenType::Eof, String::new(), 4, 1),
+    ];
+    assert_eq!(tokens, should_be);
+}
+
+#[test]
+fn test_percent() {
+    let tokens = Scanner::new("7 % 3".to_string()).scan_tokens().unwrap();
+    let should_be = vec![
+        Token::new(TokenType::Number, "7".to_string(), 1, 1),
+        Token::new(TokenType::Percent, "%".to_string(), 1, 3),
+        Token::new(TokenType::Number, "3".to_string(), 1, 5),
+        Token::new(TokenType::Eof, String::new(), 1, 6),
+    ];
+    assert_eq!(tokens, should_be);
+}
+
+#[test]
+fn test_compound_assignment_tokens() {
+    let tokens = Scanner::new("a+=1-=b*=/=c++ /".to_string())
+        .scan_tokens()
+        .unwrap();
+    let types: Vec<TokenType> = tokens.iter().map(|token| token.r#type).collect();
+    assert_eq!(
+        types,
+        [
+            TokenType::Identifier,
+            TokenType::PlusEqual,
+            TokenType::Number,
+            TokenType::MinusEqual,
+            TokenType::Identifier,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::Identifier,
+            TokenType::PlusPlus,
+            TokenType::Slash,
+            TokenType::Eof,
+        ]
+    );
+}
+
+#[test]
+fn test_keywords() {
+    let keywords = [
+        "and", "capture", "catch", "class", "elif", "else", "false", "finally", "for", "func",
+        "if", "into", "nil", "or", "pprint", "print", "return", "super", "this", "true", "try",
+        "unless", "until", "var", "while",
+    ];
+    assert_eq!(KEYWORDS.len(), keywords.len());
+    for keyword in keywords {
+        assert!(is_keyword(keyword));
+        let tokens = Scanner::new(keyword.to_string()).scan_tokens().unwrap();
+        assert_eq!(Some(tokens[0].r#type), keyword_token(keyword));
+    }
+    assert!(!is_keyword("foo"));
+    assert!(!is_keyword("While"));
+    assert_eq!(keyword_token("number"), None);
+}
+
+#[test]
+fn test_underscore_identifier() {
+    let tokens = Scanner::new("_a write_file".to_string())
+        .scan_tokens()
+        .unwrap();
+    let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+    assert_eq!(lexemes, ["_a", "write_file", ""]);
+    assert!(tokens[..2]
+        .iter()
+        .all(|token| token.r#type == TokenType::Identifier));
+}
+
+#[test]
+fn test_quotes() {
+    let data = vec![
+        (
+            "'abc'",
+            Token::new(TokenType::String, "abc".to_string(), 1, 1),
+        ),
+        (
+            "'a\"b'",
+            Token::new(TokenType::String, "a\"b".to_string(), 1, 1),
+        ),
+        (
+            "\"a'b\"",
+            Token::new(TokenType::String, "a'b".to_string(), 1, 1),
+        ),
+    ];
+    for (source_code, should_be) in data {
+        let tokens = Scanner::new(source_code.to_string()).scan_tokens().unwrap();
+        assert_eq!(tokens[0], should_be);
+    }
+
+    let token = Scanner::new("'abc\"".to_string()).scan_token().unwrap();
+    assert_eq!(token.r#type, TokenType::Invalid);
+}
+
+#[test]
+fn test_logical_aliases() {
+    let token_types = |source_code: &str| {
+        Scanner::new(source_code.to_string())
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|token| token.r#type)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(token_types("a && b"), token_types("a and b"));
+    assert_eq!(token_types("a || b"), token_types("a or b"));
+
+    let token = Scanner::new("&".to_string()).scan_token().unwrap();
+    assert_eq!(token.r#type, TokenType::Invalid);
+    assert!(token.lexeme.contains("help"));
+}
+
+#[test]
+fn test_hex_float() {
+    let data = vec![
+        ("0x1.8p3", Some(12.0)),
+        ("0x1p-1", Some(0.5)),
+        ("0XAp+2", Some(40.0)),
+        ("0xff", Some(255.0)),
+        ("0x.8p1", Some(1.0)),
+        ("0x1.8", None),
+        ("0x1.8p", None),
+        ("0xp3", None),
+        ("0x1gp3", None),
+    ];
+    for (source_code, should_be) in data {
+        let token = Scanner::new(source_code.to_string()).scan_token().unwrap();
+        assert_eq!(token.lexeme, source_code);
+        match should_be {
+            Some(value) => {
+                assert_eq!(token.r#type, TokenType::Number);
+                assert_eq!(parse_hex_float(&token.lexeme), Some(value));
+            }
+            None => assert_eq!(token.r#type, TokenType::Invalid),
+        }
+    }
+}
+
+#[test]
+fn test_block_comment() {
+    let source_code = "1 /* outer /* inner */ still\n outer */ / 2\n/* a\n\nb */ 3";
+    let tokens = Scanner::new(source_code.to_string()).scan_tokens().unwrap();
     let should_be = vec![
-        Token::new(TokenType::Var, "var".to_string(), 1),
-        Token::new(TokenType::Identifier, "id".to_string(), 1),
-        Token::new(TokenType::Equal, "=".to_string(), 1),
-        Token::new(TokenType::Number, "114.514".to_string(), 1),
-        Token::new(TokenType::Semicolon, ";".to_string(), 1),
-        Token::new(TokenType::Eof, String::new(), 1),
+        Token::new(TokenType::Number, "1".to_string(), 1, 1),
+        Token::new(TokenType::Slash, "/".to_string(), 2, 11),
+        Token::new(TokenType::Number, "2".to_string(), 2, 13),
+        Token::new(TokenType::Number, "3".to_string(), 5, 6),
+        Token::new(TokenType::Eof, String::new(), 5, 7),
     ];
     assert_eq!(tokens, should_be);
 
-    let source_code = "while (a == 114@) {\n var b = \"while\";\n }\n";
-    let tokens = Scanner::new(source_code.to_string()).scan_tokens();
+    let mut scanner = Scanner::new("/* a /* b */\n".to_string());
+    let token = scanner.scan_token().unwrap();
+    assert_eq!(token.r#type, TokenType::Invalid);
+    assert_eq!(
+        token.lexeme,
+        "unterminated block comment starting at line 1"
+    );
+    assert_eq!(scanner.scan_token(), None);
+}
+
+#[test]
+fn test_line_comment() {
+    let source_code = "1 // one / * \n/ 2 ///\n// last";
+    let tokens = Scanner::new(source_code.to_string()).scan_tokens().unwrap();
     let should_be = vec![
-        Token::new(TokenType::While, "while".to_string(), 1),
-        Token::new(TokenType::LeftParen, "(".to_string(), 1),
-        Token::new(TokenType::Identifier, "a".to_string(), 1),
-        Token::new(TokenType::EqualEqual, "==".to_string(), 1),
-        Token::new(TokenType::Number, "114".to_string(), 1),
-        Token::new(TokenType::RightParen, ")".to_string(), 1),
-        Token::new(TokenType::LeftBrace, "{".to_string(), 1),
-        Token::new(TokenType::Var, "var".to_string(), 2),
-        Token::new(TokenType::Identifier, "b".to_string(), 2),
-        Token::new(TokenType::Equal, "=".to_string(), 2),
-        Token::new(TokenType::String, "while".to_string(), 2),
-        Token::new(TokenType::Semicolon, ";".to_string(), 2),
-        Token::new(TokenType::RightBrace, "}".to_string(), 3),
-        Token::new(TokenType::Eof, String::new(), 4),
+        Token::new(TokenType::Number, "1".to_string(), 1, 1),
+        Token::new(TokenType::Slash, "/".to_string(), 2, 1),
+        Token::new(TokenType::Number, "2".to_string(), 2, 3),
+        Token::new(TokenType::Eof, String::new(), 3, 8),
     ];
     assert_eq!(tokens, should_be);
 }
+
+#[test]
+fn test_comments_keep_line_numbers() {
+    let tokens = Scanner::new("1 + /* two */ 2".to_string())
+        .scan_tokens()
+        .unwrap();
+    let expr = crate::parser::Parser::new(tokens)
+        .parse_expression()
+        .unwrap();
+    assert_eq!(expr.to_string(), "(+ 1 2)");
+
+    let source_code =
+        "var a = 1 + /* one\ntwo\n */ 2; // three\n/* four */ print\n/* five\n/* six */\n*/ a;";
+    let tokens = Scanner::new(source_code.to_string()).scan_tokens().unwrap();
+    let lines: Vec<(&str, usize)> = tokens
+        .iter()
+        .map(|token| (token.lexeme.as_str(), token.line))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            ("var", 1),
+            ("a", 1),
+            ("=", 1),
+            ("1", 1),
+            ("+", 1),
+            ("2", 3),
+            (";", 3),
+            ("print", 4),
+            ("a", 7),
+            (";", 7),
+            ("", 7),
+        ]
+    );
+    let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+    assert_eq!(stmts.len(), 2);
+}
+
+#[test]
+fn test_unterminated_string() {
+    let mut scanner = Scanner::new("print 1;\n\"no closing quote\n\n".to_string());
+    let token = std::iter::from_fn(|| scanner.scan_token())
+        .find(|token| token.r#type == TokenType::Invalid)
+        .unwrap();
+    assert_eq!(
+        token,
+        Token::new(
+            TokenType::Invalid,
+            "Unterminated string starting at line 2".to_string(),
+            2,
+            1
+        )
+    );
+}
+
+#[test]
+fn test_scan_errors() {
+    let errors = Scanner::new("@#$".to_string()).scan_tokens().unwrap_err();
+    let found: Vec<(&str, usize)> = errors
+        .iter()
+        .map(|err| (err.lexeme(), err.line()))
+        .collect();
+    assert_eq!(found, [("@", 1), ("#", 1), ("$", 1)]);
+
+    let errors = Scanner::new("var a = 1;\n@\nprint a; #".to_string())
+        .scan_tokens()
+        .unwrap_err();
+    let found: Vec<(&str, usize)> = errors
+        .iter()
+        .map(|err| (err.lexeme(), err.line()))
+        .collect();
+    assert_eq!(found, [("@", 2), ("#", 3)]);
+    assert_eq!(errors[0].to_string(), "[line 2] Invalid token: @");
+}
+
+#[test]
+fn test_large_input() {
+    // 100k characters, quadratic scanning would take minutes
+    let source_code = "var a = 1;\n".repeat(10_000);
+    assert!(source_code.len() > 100_000);
+    let started = std::time::Instant::now();
+    let tokens = Scanner::new(source_code).scan_tokens().unwrap();
+    assert_eq!(tokens.len(), 50_001);
+    assert_eq!(tokens.last().unwrap().line, 10_001);
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn test_columns() {
+    let tokens = Scanner::new("var x = 1;".to_string())
+        .scan_tokens()
+        .unwrap();
+    let columns: Vec<usize> = tokens.iter().map(|token| token.column).collect();
+    assert_eq!(columns, [1, 5, 7, 9, 10, 11]);
+
+    let tokens = Scanner::new("print 'a\nb';\n\tx;".to_string())
+        .scan_tokens()
+        .unwrap();
+    let positions: Vec<(usize, usize)> = tokens
+        .iter()
+        .map(|token| (token.line, token.column))
+        .collect();
+    // the string reports its closing line but its opening column
+    assert_eq!(positions, [(1, 1), (2, 7), (2, 3), (3, 2), (3, 3), (3, 4)]);
+}
+
+#[test]
+fn test_tab_columns() {
+    let columns = |src: &str, width: usize| -> Vec<usize> {
+        Scanner::new(src.to_string())
+            .tab_width(width)
+            .scan_tokens()
+            .unwrap()
+            .iter()
+            .map(|token| token.column)
+            .collect()
+    };
+    // by default a tab is one column like any other character
+    assert_eq!(columns("\tx", DEFAULT_TAB_WIDTH), [2, 3]);
+    assert_eq!(columns("\tx", 4), [5, 6]);
+    assert_eq!(columns("\tx", 8), [9, 10]);
+    // a tab moves to the next stop rather than a fixed distance
+    assert_eq!(columns("ab\tx", 4), [1, 5, 6]);
+    assert_eq!(columns("abcd\tx", 4), [1, 9, 10]);
+    assert_eq!(columns("\t\tx;", 2), [5, 6, 7]);
+    assert_eq!(columns("\tx\n\ty", 8), [9, 9, 10]);
+}
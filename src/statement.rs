@@ -1,43 +1,240 @@
-use crate::expression::Expr;
+use std::rc::Rc;
+
+use crate::expression::{Expr, LiteralValue};
 
 pub enum Stmt {
     Var(VarDecStmt),
     Print(PrintStmt),
     Expr(ExprStmt),
     Block(Block),
+    Capture(CaptureStmt),
+    If(IfStmt),
+    While(WhileStmt),
+    /// shared with the function values created from it
+    Function(Rc<FunctionStmt>),
+    Return(ReturnStmt),
+    Try(TryStmt),
 }
 
 pub struct Block {
     pub stmts: Vec<Stmt>,
+    /// the line of the `{`
+    pub line: usize,
 }
 
 impl Block {
-    pub fn new(stmts: Vec<Stmt>) -> Self {
-        Self { stmts }
+    pub fn new(stmts: Vec<Stmt>, line: usize) -> Self {
+        Self { stmts, line }
+    }
+}
+
+pub struct IfStmt {
+    pub condition: Expr,
+    pub then_branch: Box<Stmt>,
+    pub else_branch: Option<Box<Stmt>>,
+}
+
+impl IfStmt {
+    pub fn new(condition: Expr, then_branch: Stmt, else_branch: Option<Stmt>) -> Self {
+        Self {
+            condition,
+            then_branch: Box::new(then_branch),
+            else_branch: else_branch.map(Box::new),
+        }
+    }
+}
+
+pub struct WhileStmt {
+    pub condition: Expr,
+    pub body: Box<Stmt>,
+}
+
+impl WhileStmt {
+    pub fn new(condition: Expr, body: Stmt) -> Self {
+        Self {
+            condition,
+            body: Box::new(body),
+        }
+    }
+}
+
+/// `func name(params) { body }`, or `func (params) { body }` as an expression
+pub struct FunctionStmt {
+    /// `None` for an anonymous function
+    pub name: Option<String>,
+    pub params: Vec<String>,
+    pub body: Block,
+    /// the line of the name, or of `func` without one
+    pub line: usize,
+}
+
+impl FunctionStmt {
+    pub fn new(name: Option<String>, params: Vec<String>, body: Block, line: usize) -> Self {
+        Self {
+            name,
+            params,
+            body,
+            line,
+        }
+    }
+
+    /// the name reported in errors and traces
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("<anonymous fn>")
+    }
+}
+
+pub struct ReturnStmt {
+    /// `None` for a bare `return;`
+    pub value: Option<Expr>,
+    /// the line of the `return` keyword
+    pub line: usize,
+}
+
+impl ReturnStmt {
+    pub fn new(value: Option<Expr>, line: usize) -> Self {
+        Self { value, line }
+    }
+}
+
+/// `try { ... } catch (name) { ... } finally { ... }`, at least one of the two clauses is present
+pub struct TryStmt {
+    pub body: Block,
+    pub catch: Option<CatchClause>,
+    pub finally: Option<Block>,
+    /// the line of the `try` keyword
+    pub line: usize,
+}
+
+impl TryStmt {
+    pub fn new(
+        body: Block,
+        catch: Option<CatchClause>,
+        finally: Option<Block>,
+        line: usize,
+    ) -> Self {
+        Self {
+            body,
+            catch,
+            finally,
+            line,
+        }
+    }
+}
+
+/// `catch (name) { ... }`, `name` is bound to the message of the caught error
+pub struct CatchClause {
+    pub var_name: String,
+    pub body: Block,
+}
+
+/// `capture { ... } into name;` binds everything the block prints to `name`
+pub struct CaptureStmt {
+    pub body: Block,
+    pub var_name: String,
+    /// the line of the `capture` keyword
+    pub line: usize,
+}
+
+impl CaptureStmt {
+    pub fn new(body: Block, var_name: String, line: usize) -> Self {
+        Self {
+            body,
+            var_name,
+            line,
+        }
     }
 }
 
 pub struct VarDecStmt {
     pub var_name: String,
     pub initializer: Option<Expr>,
+    /// the type after `:`, checked at runtime on every assignment
+    pub annotation: Option<TypeAnnotation>,
+    /// the line of the name
+    pub line: usize,
 }
 
 impl VarDecStmt {
-    pub fn new(var_name: String, initializer: Option<Expr>) -> Self {
+    pub fn new(
+        var_name: String,
+        initializer: Option<Expr>,
+        annotation: Option<TypeAnnotation>,
+        line: usize,
+    ) -> Self {
         Self {
             var_name,
             initializer,
+            annotation,
+            line,
         }
     }
 }
 
+/// type annotation like `var x: number = 1;`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TypeAnnotation {
+    Number,
+    String,
+    Bool,
+    Nil,
+    Any,
+}
+
+impl TypeAnnotation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "number" => Some(TypeAnnotation::Number),
+            "string" => Some(TypeAnnotation::String),
+            "bool" => Some(TypeAnnotation::Bool),
+            "nil" => Some(TypeAnnotation::Nil),
+            "any" => Some(TypeAnnotation::Any),
+            _ => None,
+        }
+    }
+
+    /// whether a value of this type can be stored in the binding
+    pub fn accepts(&self, value: &LiteralValue) -> bool {
+        matches!(
+            (self, value),
+            (TypeAnnotation::Any, _)
+                | (TypeAnnotation::Number, LiteralValue::Num(_))
+                | (TypeAnnotation::String, LiteralValue::Str(_))
+                | (TypeAnnotation::Bool, LiteralValue::Bool(_))
+                | (TypeAnnotation::Nil, LiteralValue::Nil)
+        )
+    }
+}
+
+impl std::fmt::Display for TypeAnnotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TypeAnnotation::Number => "number",
+            TypeAnnotation::String => "string",
+            TypeAnnotation::Bool => "bool",
+            TypeAnnotation::Nil => "nil",
+            TypeAnnotation::Any => "any",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 pub struct PrintStmt {
     pub expr: Expr,
+    /// `pprint`, collections are laid out over indented lines
+    pub pretty: bool,
 }
 
 impl PrintStmt {
     pub fn new(expr: Expr) -> Self {
-        Self { expr }
+        Self {
+            expr,
+            pretty: false,
+        }
+    }
+
+    pub fn pretty(expr: Expr) -> Self {
+        Self { expr, pretty: true }
     }
 }
 pub struct ExprStmt {
@@ -49,3 +246,111 @@ impl ExprStmt {
         Self { expr }
     }
 }
+
+impl std::fmt::Display for Stmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stmt::Var(var) => var.fmt(f),
+            Stmt::Print(print) if print.pretty => write!(f, "(pprint {})", print.expr),
+            Stmt::Print(print) => write!(f, "(print {})", print.expr),
+            Stmt::Expr(expr) => write!(f, "{}", expr.expr),
+            Stmt::Block(block) => block.fmt(f),
+            Stmt::Capture(capture) => {
+                write!(f, "(capture {} into {})", capture.body, capture.var_name)
+            }
+            Stmt::If(if_stmt) => if_stmt.fmt(f),
+            Stmt::While(while_stmt) => {
+                write!(f, "(while {} {})", while_stmt.condition, while_stmt.body)
+            }
+            Stmt::Function(function) => function.fmt(f),
+            Stmt::Return(return_stmt) => match &return_stmt.value {
+                Some(value) => write!(f, "(return {})", value),
+                None => write!(f, "(return)"),
+            },
+            Stmt::Try(try_stmt) => {
+                write!(f, "(try {}", try_stmt.body)?;
+                if let Some(catch) = &try_stmt.catch {
+                    write!(f, " (catch {} {})", catch.var_name, catch.body)?;
+                }
+                if let Some(finally) = &try_stmt.finally {
+                    write!(f, " (finally {})", finally)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Block {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(block")?;
+        for stmt in &self.stmts {
+            write!(f, " {}", stmt)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Display for FunctionStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(func ")?;
+        if let Some(name) = &self.name {
+            write!(f, "{} ", name)?;
+        }
+        write!(f, "({}) {})", self.params.join(" "), self.body)
+    }
+}
+
+impl std::fmt::Display for IfStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(if {} {}", self.condition, self.then_branch)?;
+        if let Some(else_branch) = &self.else_branch {
+            write!(f, " {}", else_branch)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::fmt::Display for VarDecStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(var {}", self.var_name)?;
+        if let Some(annotation) = &self.annotation {
+            write!(f, ": {}", annotation)?;
+        }
+        if let Some(initializer) = &self.initializer {
+            write!(f, " = {}", initializer)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[test]
+fn test_display_functions() {
+    let src = "func f(a, b) { return a; } var g = func (x) { print x; };";
+    let tokens = crate::scanner::Scanner::new(src.to_string())
+        .scan_tokens()
+        .unwrap();
+    let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+    let rendered: Vec<String> = stmts.iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(
+        rendered,
+        [
+            "(func f (a b) (block (return a)))",
+            "(var g = (func (x) (block (print x))))"
+        ]
+    );
+}
+
+#[test]
+fn test_display_nested_block() {
+    let src = "var a; { var b = 1; print a; { a = b; } }";
+    let tokens = crate::scanner::Scanner::new(src.to_string())
+        .scan_tokens()
+        .unwrap();
+    let stmts = crate::parser::Parser::new(tokens).parse().unwrap();
+    let rendered: Vec<String> = stmts.iter().map(|stmt| stmt.to_string()).collect();
+    assert_eq!(
+        rendered,
+        ["(var a)", "(block (var b = 1) (print a) (block a = b))"]
+    );
+}
@@ -5,14 +5,17 @@ pub struct Token {
     pub r#type: TokenType,
     pub lexeme: String,
     pub line: usize,
+    /// 1-based column of the first character
+    pub column: usize,
 }
 
 impl Token {
-    pub fn new(r#type: TokenType, lexeme: String, line: usize) -> Self {
+    pub fn new(r#type: TokenType, lexeme: String, line: usize, column: usize) -> Self {
         Self {
             r#type,
             lexeme,
             line,
+            column,
         }
     }
 }
@@ -24,15 +27,26 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
+    Question,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Percent,
 
     // One or two character tokens.
+    MinusMinus,
+    PlusPlus,
+    MinusEqual,
+    PlusEqual,
+    StarEqual,
+    SlashEqual,
     Bang,
     BangEqual,
     Equal,
@@ -49,19 +63,28 @@ pub enum TokenType {
 
     // Keywords.
     And,
+    Capture,
+    Catch,
     Class,
+    Elif,
     Else,
     False,
+    Finally,
     Func,
     For,
     If,
+    Into,
     Nil,
     Or,
+    Pprint,
     Print,
     Return,
     Super,
     This,
     True,
+    Try,
+    Unless,
+    Until,
     Var,
     While,
 
@@ -77,13 +100,24 @@ impl Display for TokenType {
             TokenType::RightParen => ")",
             TokenType::LeftBrace => "{",
             TokenType::RightBrace => "}",
+            TokenType::LeftBracket => "[",
+            TokenType::RightBracket => "]",
             TokenType::Comma => ",",
+            TokenType::Colon => ":",
+            TokenType::Question => "?",
             TokenType::Dot => ".",
             TokenType::Minus => "-",
             TokenType::Plus => "+",
             TokenType::Semicolon => ";",
             TokenType::Slash => "/",
             TokenType::Star => "*",
+            TokenType::Percent => "%",
+            TokenType::MinusMinus => "--",
+            TokenType::PlusPlus => "++",
+            TokenType::MinusEqual => "-=",
+            TokenType::PlusEqual => "+=",
+            TokenType::StarEqual => "*=",
+            TokenType::SlashEqual => "/=",
             TokenType::Bang => "!",
             TokenType::BangEqual => "!=",
             TokenType::Equal => "=",
@@ -95,26 +129,35 @@ impl Display for TokenType {
             TokenType::Identifier => "Identifier",
             TokenType::String => "String",
             TokenType::Number => "Number",
-            TokenType::And => "&&",
+            TokenType::And => "and",
+            TokenType::Capture => "capture",
+            TokenType::Catch => "catch",
             TokenType::Class => "class",
+            TokenType::Elif => "elif",
             TokenType::Else => "else",
             TokenType::False => "false",
+            TokenType::Finally => "finally",
             TokenType::Func => "func",
             TokenType::For => "for",
             TokenType::If => "if",
+            TokenType::Into => "into",
             TokenType::Nil => "nil",
             TokenType::Or => "or",
+            TokenType::Pprint => "pprint",
             TokenType::Print => "print",
             TokenType::Return => "return",
             TokenType::Super => "super",
             TokenType::This => "this",
             TokenType::True => "true",
+            TokenType::Try => "try",
+            TokenType::Unless => "unless",
+            TokenType::Until => "until",
             TokenType::Var => "var",
             TokenType::While => "while",
             TokenType::Eof => "Eof",
             TokenType::Invalid => "Invalid",
             TokenType::Blank => "Blank",
         };
-        write!(f, "{}", str.to_string())
+        write!(f, "{}", str)
     }
 }
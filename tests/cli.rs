@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// write `source` to a temporary .lox file and run the binary on it
+fn run_source(name: &str, source: &str) -> Option<i32> {
+    let path: PathBuf =
+        std::env::temp_dir().join(format!("rlox_exit_{}_{}.lox", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg(&path)
+        .output()
+        .unwrap()
+        .status;
+    std::fs::remove_file(&path).unwrap();
+    status.code()
+}
+
+#[test]
+fn test_exit_codes() {
+    assert_eq!(run_source("ok", "print 1;"), Some(0));
+    assert_eq!(run_source("parse", "print 1; var 2;"), Some(65));
+    assert_eq!(run_source("scan", "print 1 # 2;"), Some(65));
+    assert_eq!(run_source("runtime", "print 1; print -\"a\";"), Some(70));
+}
+
+#[test]
+fn test_missing_file_exit_code() {
+    let status = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("/no/such/file.lox")
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(74));
+}
+
+#[test]
+fn test_tokens_flag() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("--tokens")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"print 1 + x;")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Print \"print\" 1\nNumber \"1\" 1\nPlus \"+\" 1\nIdentifier \"x\" 1\nSemicolon \";\" 1\nEof \"\" 1\n"
+    );
+}
+
+#[test]
+fn test_ast_flag() {
+    let path = std::env::temp_dir().join(format!("rlox_ast_{}.lox", std::process::id()));
+    std::fs::write(&path, "print 1 + 2;").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("--ast")
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "(print (+ 1 2))\n");
+}
+
+#[test]
+fn test_eval_flag() {
+    let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["-e", "print 1+2; var a = \"x\"; print a * 3;"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\nxxx\n");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(["--eval", "print -nil;"])
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(70));
+}
+
+#[test]
+fn test_program_from_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"var a = 2;\nprint a * 3;\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    // run once, no prompt is shown
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "6\n");
+}
+
+#[test]
+fn test_check_flag() {
+    let check = |name: &str, source: &str| {
+        let path =
+            std::env::temp_dir().join(format!("rlox_check_{}_{}.lox", name, std::process::id()));
+        std::fs::write(&path, source).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_rlox"))
+            .arg("--check")
+            .arg(&path)
+            .output()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        output
+    };
+
+    let output = check("parse", "print 1;\nvar 2;\n");
+    assert_eq!(output.status.code(), Some(65));
+
+    // checked but never run, so neither the print nor the runtime error happens
+    let output = check("ok", "print 1;\nprint -\"a\";\n");
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+}